@@ -1,63 +1,299 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 // Linting
 #![warn(rust_2018_idioms)]
 #![deny(clippy::all)]
 #![warn(clippy::pedantic, clippy::nursery)]
 #![allow(clippy::module_name_repetitions)]
 
+#[macro_use]
+extern crate alloc;
 #[cfg(test)]
 #[macro_use]
 extern crate approx;
 
 mod managed_octree;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{
+    borrow::{Borrow, BorrowMut},
+    convert::{AsMut, AsRef},
+    fmt::{self, Debug},
+};
 pub use managed_octree::{
-    ManagedHashMapOctree,
+    Axis,
+    BoundsMismatchError,
+    BuildError,
+    CentredItem,
+    Chebyshev,
+    Euclidean,
+    InvariantError,
+    ManagedBTreeMapOctree,
     ManagedOctree,
     ManagedOctreeData,
+    ManagedOctreeIntoIter,
     ManagedVecOctree,
+    Manhattan,
+    MassDistribution,
+    Metric,
+    MovableItem,
+    OctreeBuilder,
+    OctreeCollection,
+    OctreeCollectionRef,
+    OctreeCollectionSize,
+    OctreeConfig,
+    OutOfBoundsError,
+    SplitStrategy,
+    Weighted,
+    octant_index,
 };
-use std::{
-    borrow::{Borrow, BorrowMut},
-    convert::{AsMut, AsRef},
-};
+#[cfg(feature = "std")]
+pub use managed_octree::{Entry, ManagedHashMapOctree, VacantEntry};
+
+/// The deepest level of children displayed when debug formatting an octree
+/// without the alternate (`{:#?}`) flag.
+const DEBUG_MAX_DEPTH: u32 = 4;
+
+/// A barebones tree whose nodes each have up to `N` children, offering just
+/// the methods required for accessing and modifying its contents.
+///
+/// This is usually used through the `Octree` (`N` = 8) and `Quadtree` (`N` =
+/// 4) aliases, which add methods for accessing children by the side of each
+/// axis they lie on.
+///
+/// Other management structures/functions will be needed to make this more
+/// useful, especially for the purpose of querying contents.
+///
+/// The node data needn't implement `Default`, though creating default children
+/// with `new` or `get_or_create_child` requires it.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tree<D, const N: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "serde_children"))]
+    children: [Option<Box<Self>>; N],
+    data: D,
+}
 
-/// A barebones octree offering just the methods required for accessing and
-/// modifying its contents. Other management structures/functions will be needed
-/// to make this more useful, especially for the purpose of querying contents.
-#[derive(Default)]
-pub struct Octree<D>
+/// A tree dividing each node into eight octants along the x, y and z axes.
+pub type Octree<D> = Tree<D, 8>;
+
+/// A tree dividing each node into four quadrants along the x and y axes.
+pub type Quadtree<D> = Tree<D, 4>;
+
+impl<D, const N: usize> Default for Tree<D, N>
 where
     D: Default,
 {
-    children: [Option<Box<Octree<D>>>; 8],
-    data: D,
+    fn default() -> Self { Self::new_with_data(D::default()) }
+}
+
+impl<D, const N: usize> Clone for Tree<D, N>
+where
+    D: Clone,
+{
+    /// Deep copies the node's data along with all of its children.
+    fn clone(&self) -> Self {
+        Self {
+            children: self.children.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<D, const N: usize> PartialEq for Tree<D, N>
+where
+    D: PartialEq,
+{
+    /// Compares the data of each node along with which children are present,
+    /// so trees holding the same data arranged differently aren't equal.
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data && self.children == other.children
+    }
+}
+
+impl<D, const N: usize> Eq for Tree<D, N> where D: Eq {}
+
+impl<D, const N: usize> Debug for Tree<D, N>
+where
+    D: Debug,
+{
+    /// Formats the node's data followed by each present child and its index,
+    /// indented by depth. Children deeper than `DEBUG_MAX_DEPTH` are elided
+    /// unless the alternate flag (`{:#?}`) is used.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max_depth = if f.alternate() {
+            None
+        } else {
+            Some(DEBUG_MAX_DEPTH)
+        };
+        self.fmt_to_depth(f, 0, max_depth)
+    }
+}
+
+impl<D, const N: usize> Tree<D, N>
+where
+    D: Debug,
+{
+    fn fmt_to_depth(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        depth: u32,
+        max_depth: Option<u32>,
+    ) -> fmt::Result {
+        write!(f, "{:?}", self.data)?;
+        for (idx, child) in self.children.iter().enumerate() {
+            if let Some(child) = child {
+                let indent = (depth as usize + 1) * 2;
+                write!(f, "\n{:indent$}[{}] ", "", idx, indent = indent)?;
+                if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                    write!(f, "...")?;
+                } else {
+                    child.fmt_to_depth(f, depth + 1, max_depth)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A breadth-first iterator over the nodes of a tree along with their depth
+/// and Morton code.
+struct Nodes<'a, D, const N: usize> {
+    queue: VecDeque<(&'a Tree<D, N>, u32, u64)>,
+}
+
+impl<'a, D, const N: usize> Iterator for Nodes<'a, D, N> {
+    type Item = (&'a Tree<D, N>, u32, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth, code) = self.queue.pop_front()?;
+        self.queue
+            .extend(node.children.iter().enumerate().filter_map(
+                |(idx, child)| {
+                    child.as_ref().map(|child| {
+                        (
+                            child.as_ref(),
+                            depth + 1,
+                            code << N.trailing_zeros() | idx as u64,
+                        )
+                    })
+                },
+            ));
+        Some((node, depth, code))
+    }
+}
+
+/// A node of a tree linearised by `flatten`, which refers to other nodes by
+/// their index in the flattened `Vec` rather than by pointer.
+#[derive(Debug)]
+pub struct FlatNode<'a, D, const N: usize = 8> {
+    /// The index of this node's parent, or `None` for the flattened root.
+    pub parent: Option<u64>,
+    /// The index of the child at each child index, if present.
+    pub children: [Option<u64>; N],
+    pub data: &'a D,
+}
+
+/// An axis-aligned direction, used to find a node's neighbour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Direction {
+    /// Gets the bit of a child index that is set for children on the positive
+    /// side of this direction's axis, and whether the direction is positive.
+    const fn axis_bit(self) -> (usize, bool) {
+        match self {
+            Self::PosX => (4, true),
+            Self::NegX => (4, false),
+            Self::PosY => (2, true),
+            Self::NegY => (2, false),
+            Self::PosZ => (1, true),
+            Self::NegZ => (1, false),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum AddChildError {
     AlreadyAdded,
     OutOfBoundsIdx,
+    MissingParent,
 }
 
-impl<D> Octree<D>
+impl<D, const N: usize> Tree<D, N>
 where
     D: Default,
 {
     #[must_use]
     pub fn new() -> Self { Self::default() }
 
+    /// Returns a reference to the child at a particular index, adding a
+    /// default child first if there isn't one.
+    ///
+    /// # Errors
+    /// Returns an error if the idx is out of range (i.e. idx >= N).
+    pub fn get_or_create_child(
+        &mut self,
+        idx: usize,
+    ) -> Result<&mut Self, AddChildError> {
+        self.children
+            .get_mut(idx)
+            .ok_or(AddChildError::OutOfBoundsIdx)
+            .map(|child| child.get_or_insert_with(Box::default).as_mut())
+    }
+}
+
+impl<D> Octree<D>
+where
+    D: Default,
+{
+    /// Returns a reference to the child at an index based on whether the child
+    /// is at the positive or negative side of each axis, adding a default
+    /// child first if there isn't one.
+    ///
+    /// # Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    /// * `pos_z` - positive z axis if true, negative if false.
+    pub fn get_or_create_child_at_pos(
+        &mut self,
+        pos_x: bool,
+        pos_y: bool,
+        pos_z: bool,
+    ) -> &mut Self {
+        let idx = Self::get_child_idx_at_pos(pos_x, pos_y, pos_z);
+        self.children[idx].get_or_insert_with(Box::default)
+    }
+}
+
+impl<D, const N: usize> Tree<D, N> {
+    /// Creates a node without children holding `data`. Unlike `new`, this
+    /// doesn't need `D` to implement `Default`.
     #[must_use]
     pub fn new_with_data(data: D) -> Self {
         Self {
+            children: core::array::from_fn(|_| None),
             data,
-            ..Self::default()
         }
     }
 
     /// Adds and returns a reference to a child at a particular index.
     ///
+    /// # Warning
+    /// On a managed octree this leaves the `len` of this node and all of its
+    /// ancestors without the added child's items. Use `add_subtree` there
+    /// instead, which keeps every count along the path correct.
+    ///
     /// # Errors
-    /// Returns an error if the idx is out of range (i.e. idx >= 8) or if the
+    /// Returns an error if the idx is out of range (i.e. idx >= N) or if the
     /// child is already added.
     pub fn add_child(
         &mut self,
@@ -74,6 +310,217 @@ where
         }
     }
 
+    /// Removes a child and returns the owned value, if it exists.
+    ///
+    /// # Warning
+    /// On a managed octree this leaves the `len` of this node and all of its
+    /// ancestors still counting the removed items. Use `remove_subtree` there
+    /// instead, which keeps every count along the path correct.
+    pub fn remove_child(&mut self, idx: usize) -> Option<Self> {
+        if self.children.get(idx).is_none() {
+            None
+        } else {
+            self.children[idx].take().map(|c| *c)
+        }
+    }
+
+    /// Gets a reference to a child given an index.
+    #[must_use]
+    pub fn get_child(&self, idx: usize) -> Option<&Self> {
+        if idx >= self.children.len() {
+            None
+        } else {
+            self.children[idx].as_ref().map(AsRef::as_ref)
+        }
+    }
+
+    /// Gets a mutable reference to a child given an index.
+    #[must_use]
+    pub fn get_child_mut(&mut self, idx: usize) -> Option<&mut Self> {
+        if idx >= self.children.len() {
+            None
+        } else {
+            self.children[idx].as_mut().map(AsMut::as_mut)
+        }
+    }
+
+    /// Gets a reference to a descendant by following each child index in
+    /// `path` in turn, returning `None` if any step is missing or out of
+    /// range. The empty path gets this node.
+    #[must_use]
+    pub fn get_by_path(&self, path: &[usize]) -> Option<&Self> {
+        path.iter().try_fold(self, |node, &idx| node.get_child(idx))
+    }
+
+    /// Gets a mutable reference to a descendant by following each child index
+    /// in `path` in turn. See `get_by_path`.
+    #[must_use]
+    pub fn get_by_path_mut(&mut self, path: &[usize]) -> Option<&mut Self> {
+        path.iter()
+            .try_fold(self, |node, &idx| node.get_child_mut(idx))
+    }
+
+    /// Gets a reference to the underlying data in the node.
+    #[must_use]
+    pub fn get_data(&self) -> &D { self.data.borrow() }
+
+    /// Gets a mutable reference to the underlying data in the node.
+    #[must_use]
+    pub fn get_data_mut(&mut self) -> &mut D { self.data.borrow_mut() }
+
+    /// Iterates over the children that are present, along with their index.
+    pub fn children(&self) -> impl Iterator<Item = (usize, &Self)> {
+        self.children
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, child)| Some((idx, child.as_deref()?)))
+    }
+
+    /// Iterates mutably over the children that are present, along with their
+    /// index.
+    pub fn children_mut(&mut self) -> impl Iterator<Item = (usize, &mut Self)> {
+        self.children
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, child)| Some((idx, child.as_deref_mut()?)))
+    }
+
+    /// Checks whether this node has no children.
+    #[must_use]
+    pub fn is_leaf(&self) -> bool { self.children.iter().all(Option::is_none) }
+
+    /// Counts the children directly beneath this node.
+    #[must_use]
+    pub fn child_count(&self) -> usize {
+        self.children.iter().flatten().count()
+    }
+
+    /// Counts this node and all of its descendants.
+    #[must_use]
+    pub fn node_count(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .flatten()
+            .map(|child| child.node_count())
+            .sum::<usize>()
+    }
+
+    /// Counts the nodes in this subtree that have no children.
+    #[must_use]
+    pub fn leaf_count(&self) -> usize {
+        if self.is_leaf() {
+            1
+        } else {
+            self.children
+                .iter()
+                .flatten()
+                .map(|child| child.leaf_count())
+                .sum()
+        }
+    }
+
+    /// Folds over this node and all of its descendants depth-first, visiting
+    /// each node before its children (which are visited in index order).
+    pub fn fold_nodes<A, F>(&self, init: A, mut f: F) -> A
+    where
+        F: FnMut(A, &Self) -> A,
+    {
+        self.fold_nodes_with(init, &mut f)
+    }
+
+    fn fold_nodes_with<A, F>(&self, init: A, f: &mut F) -> A
+    where
+        F: FnMut(A, &Self) -> A,
+    {
+        let acc = f(init, self);
+        self.children
+            .iter()
+            .flatten()
+            .fold(acc, |acc, child| child.fold_nodes_with(acc, f))
+    }
+
+    /// Converts the data of this node and all of its descendants with `f`,
+    /// keeping the structure of the tree. Nodes are visited in the same order
+    /// as `fold_nodes`.
+    pub fn map_data<E, F>(self, mut f: F) -> Tree<E, N>
+    where
+        F: FnMut(D) -> E,
+    {
+        self.map_data_with(&mut f)
+    }
+
+    fn map_data_with<E, F>(self, f: &mut F) -> Tree<E, N>
+    where
+        F: FnMut(D) -> E,
+    {
+        let mut mapped = Tree::new_with_data(f(self.data));
+        for (slot, child) in mapped
+            .children
+            .iter_mut()
+            .zip(IntoIterator::into_iter(self.children))
+        {
+            *slot = child.map(|child| Box::new(child.map_data_with(f)));
+        }
+        mapped
+    }
+
+    /// Iterates breadth-first over this node and all of its descendants, along
+    /// with their depth relative to this node (which has a depth of 0).
+    pub fn iter_nodes(&self) -> impl Iterator<Item = (&Self, u32)> {
+        self.nodes().map(|(node, depth, _)| (node, depth))
+    }
+
+    /// Iterates breadth-first over this node and all of its descendants, along
+    /// with their Morton (Z-order) code relative to this node.
+    ///
+    /// A node's code is the child indices along the path from this node
+    /// packed 3 bits at a time (2 for a `Quadtree`), with the first step in the
+    /// most significant position: this node has code 0, its child `idx` has
+    /// code `idx`, and that child's child `j` has code `idx << 3 | j`. Codes
+    /// are therefore only unique among nodes of the same depth, and nodes
+    /// deeper than 21 levels (32 for a `Quadtree`) lose their leading steps.
+    /// Within each depth, nodes are yielded in ascending code order.
+    pub fn iter_nodes_morton(&self) -> impl Iterator<Item = (u64, &Self)> {
+        self.nodes().map(|(node, _, code)| (code, node))
+    }
+
+    /// Linearises this node and all of its descendants into a `Vec` in the
+    /// breadth-first order of `iter_nodes`, so this node is at index 0 and
+    /// each node's children follow its siblings' in ascending child index.
+    /// Nodes refer to their parent and children by index, which makes the
+    /// result suitable for uploading to the GPU.
+    #[must_use]
+    pub fn flatten(&self) -> Vec<FlatNode<'_, D, N>> {
+        let mut flat = Vec::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((self, None));
+        let mut next = 1;
+        while let Some((node, parent)) = queue.pop_front() {
+            let idx = flat.len() as u64;
+            let mut children = [None; N];
+            for (child_idx, child) in node.children() {
+                children[child_idx] = Some(next);
+                next += 1;
+                queue.push_back((child, Some(idx)));
+            }
+            flat.push(FlatNode {
+                parent,
+                children,
+                data: &node.data,
+            });
+        }
+        flat
+    }
+
+    fn nodes(&self) -> Nodes<'_, D, N> {
+        let mut queue = VecDeque::new();
+        queue.push_back((self, 0, 0));
+        Nodes { queue }
+    }
+}
+
+impl<D> Octree<D> {
     /// Adds and returns a reference to a child at an index based on whether the
     /// child is at the positive or negative side of each axis.
     ///
@@ -82,6 +529,10 @@ where
     /// * `pos_y` - positive y axis if true, negative if false.
     /// * `pos_z` - positive z axis if true, negative if false.
     ///
+    /// # Warning
+    /// As with `add_child`, this leaves a managed octree's `len` stale; use
+    /// `add_subtree` there instead.
+    ///
     /// # Errors
     /// Returns an error if the child is already added.
     pub fn add_child_at_pos(
@@ -94,15 +545,6 @@ where
         self.add_child(Self::get_child_idx_at_pos(pos_x, pos_y, pos_z), child)
     }
 
-    /// Removes a child and returns the owned value, if it exists.
-    pub fn remove_child(&mut self, idx: usize) -> Option<Self> {
-        if self.children.get(idx).is_none() {
-            None
-        } else {
-            self.children[idx].take().map(|c| *c)
-        }
-    }
-
     /// Removes a child at an index based on whether the child is at the
     /// positive or negative side of each access and returns the owned value, if
     /// it exists.
@@ -111,6 +553,10 @@ where
     /// * `pos_x` - positive x axis if true, negative if false.
     /// * `pos_y` - positive y axis if true, negative if false.
     /// * `pos_z` - positive z axis if true, negative if false.
+    ///
+    /// # Warning
+    /// As with `remove_child`, this leaves a managed octree's `len` stale; use
+    /// `remove_subtree` there instead.
     pub fn remove_child_at_pos(
         &mut self,
         pos_x: bool,
@@ -120,24 +566,25 @@ where
         self.remove_child(Self::get_child_idx_at_pos(pos_x, pos_y, pos_z))
     }
 
-    /// Gets a reference to a child given an index.
+    /// Gets the node of the same size adjacent to the descendant at `path`
+    /// (see `get_by_path`) in direction `dir`.
+    ///
+    /// Returns `None` if that node lies on the boundary of this node in `dir`,
+    /// or if the neighbour hasn't been subdivided down to the same depth.
     #[must_use]
-    pub fn get_child(&self, idx: usize) -> Option<&Self> {
-        if idx >= self.children.len() {
-            None
-        } else {
-            self.children[idx].as_ref().map(AsRef::as_ref)
-        }
-    }
-
-    /// Gets a mutable reference to a child given an index.
-    #[must_use]
-    pub fn get_child_mut(&mut self, idx: usize) -> Option<&mut Self> {
-        if idx >= self.children.len() {
-            None
-        } else {
-            self.children[idx].as_mut().map(AsMut::as_mut)
+    pub fn neighbour(&self, path: &[usize], dir: Direction) -> Option<&Self> {
+        let (bit, positive) = dir.axis_bit();
+        let mut neighbour_path = path.to_vec();
+        // Step across the face at the deepest level that has room on the side
+        // of `dir`, wrapping around to the opposite side at each level below.
+        for idx in neighbour_path.iter_mut().rev() {
+            let wraps = (*idx & bit != 0) == positive;
+            *idx ^= bit;
+            if !wraps {
+                return self.get_by_path(&neighbour_path);
+            }
         }
+        None
     }
 
     /// Gets a child index given whether the child is at the positive or
@@ -147,7 +594,12 @@ where
     /// * `pos_x` - positive x axis if true, negative if false.
     /// * `pos_y` - positive y axis if true, negative if false.
     /// * `pos_z` - positive z axis if true, negative if false.
-    fn get_child_idx_at_pos(pos_x: bool, pos_y: bool, pos_z: bool) -> usize {
+    #[must_use]
+    pub const fn get_child_idx_at_pos(
+        pos_x: bool,
+        pos_y: bool,
+        pos_z: bool,
+    ) -> usize {
         match (pos_x, pos_y, pos_z) {
             (false, false, false) => 0,
             (false, false, true) => 1,
@@ -160,18 +612,25 @@ where
         }
     }
 
-    /// Panics if idx > 7
-    fn get_child_pos_at_idx(idx: usize) -> (bool, bool, bool) {
+    /// Gets whether a child is at the positive or negative side of each axis
+    /// given its index, as `(pos_x, pos_y, pos_z)`. This is the inverse of
+    /// `get_child_idx_at_pos`.
+    ///
+    /// Returns `None` if the idx is out of range (i.e. idx >= 8).
+    #[must_use]
+    pub const fn get_child_pos_at_idx(
+        idx: usize,
+    ) -> Option<(bool, bool, bool)> {
         match idx {
-            0 => (false, false, false),
-            1 => (false, false, true),
-            2 => (false, true, false),
-            3 => (false, true, true),
-            4 => (true, false, false),
-            5 => (true, false, true),
-            6 => (true, true, false),
-            7 => (true, true, true),
-            _ => panic!("idx > 7"),
+            0 => Some((false, false, false)),
+            1 => Some((false, false, true)),
+            2 => Some((false, true, false)),
+            3 => Some((false, true, true)),
+            4 => Some((true, false, false)),
+            5 => Some((true, false, true)),
+            6 => Some((true, true, false)),
+            7 => Some((true, true, true)),
+            _ => None,
         }
     }
 
@@ -208,19 +667,202 @@ where
     ) -> Option<&mut Self> {
         self.get_child_mut(Self::get_child_idx_at_pos(pos_x, pos_y, pos_z))
     }
+}
 
-    /// Gets a reference to the underlying data in the node.
+impl<D> Quadtree<D>
+where
+    D: Default,
+{
+    /// Returns a reference to the child at an index based on whether the child
+    /// is at the positive or negative side of each axis, adding a default
+    /// child first if there isn't one.
+    ///
+    /// # Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    pub fn get_or_create_child_at_pos(
+        &mut self,
+        pos_x: bool,
+        pos_y: bool,
+    ) -> &mut Self {
+        let idx = Self::get_child_idx_at_pos(pos_x, pos_y);
+        self.children[idx].get_or_insert_with(Box::default)
+    }
+}
+
+impl<D> Quadtree<D> {
+    /// Adds and returns a reference to a child at an index based on whether the
+    /// child is at the positive or negative side of each axis.
+    ///
+    /// # Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    ///
+    /// # Warning
+    /// As with `add_child`, this leaves a managed octree's `len` stale; use
+    /// `add_subtree` there instead.
+    ///
+    /// # Errors
+    /// Returns an error if the child is already added.
+    pub fn add_child_at_pos(
+        &mut self,
+        pos_x: bool,
+        pos_y: bool,
+        child: Self,
+    ) -> Result<&mut Self, AddChildError> {
+        self.add_child(Self::get_child_idx_at_pos(pos_x, pos_y), child)
+    }
+
+    /// Removes a child at an index based on whether the child is at the
+    /// positive or negative side of each axis and returns the owned value, if
+    /// it exists.
+    ///
+    /// # Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    ///
+    /// # Warning
+    /// As with `remove_child`, this leaves a managed octree's `len` stale; use
+    /// `remove_subtree` there instead.
+    pub fn remove_child_at_pos(
+        &mut self,
+        pos_x: bool,
+        pos_y: bool,
+    ) -> Option<Self> {
+        self.remove_child(Self::get_child_idx_at_pos(pos_x, pos_y))
+    }
+
+    /// Gets a child index given whether the child is at the positive or
+    /// negative side of an axis, using the same bit for each axis as an
+    /// `Octree` would for y and z.
+    ///
+    /// ## Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
     #[must_use]
-    pub fn get_data(&self) -> &D { self.data.borrow() }
+    pub const fn get_child_idx_at_pos(pos_x: bool, pos_y: bool) -> usize {
+        match (pos_x, pos_y) {
+            (false, false) => 0,
+            (false, true) => 1,
+            (true, false) => 2,
+            (true, true) => 3,
+        }
+    }
 
-    /// Gets a mutable reference to the underlying data in the node.
+    /// Gets whether a child is at the positive or negative side of each axis
+    /// given its index, as `(pos_x, pos_y)`. This is the inverse of
+    /// `get_child_idx_at_pos`.
+    ///
+    /// Returns `None` if the idx is out of range (i.e. idx >= 4).
     #[must_use]
-    pub fn get_data_mut(&mut self) -> &mut D { self.data.borrow_mut() }
+    pub const fn get_child_pos_at_idx(idx: usize) -> Option<(bool, bool)> {
+        match idx {
+            0 => Some((false, false)),
+            1 => Some((false, true)),
+            2 => Some((true, false)),
+            3 => Some((true, true)),
+            _ => None,
+        }
+    }
+
+    /// Gets a reference to a child given whether the child is at the positive
+    /// or negative side of an axis.
+    ///
+    /// ## Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    #[must_use]
+    pub fn get_child_at_pos(&self, pos_x: bool, pos_y: bool) -> Option<&Self> {
+        self.get_child(Self::get_child_idx_at_pos(pos_x, pos_y))
+    }
+
+    /// Gets a mutable reference to a child given whether the child is at the
+    /// positive or negative side of an axis.
+    ///
+    /// ## Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    #[must_use]
+    pub fn get_child_mut_at_pos(
+        &mut self,
+        pos_x: bool,
+        pos_y: bool,
+    ) -> Option<&mut Self> {
+        self.get_child_mut(Self::get_child_idx_at_pos(pos_x, pos_y))
+    }
+}
+
+/// Serializes a node's children in the form serde uses for fixed-size arrays,
+/// which it only implements for lengths it knows in advance, not for `N`.
+#[cfg(feature = "serde")]
+mod serde_children {
+    use alloc::boxed::Box;
+    use core::{fmt, marker::PhantomData};
+    use serde::{
+        Deserialize,
+        Deserializer,
+        Serialize,
+        Serializer,
+        de::{self, SeqAccess, Visitor},
+        ser::SerializeTuple,
+    };
+
+    pub fn serialize<T, S, const N: usize>(
+        children: &[Option<Box<T>>; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for child in children {
+            tuple.serialize_element(child)?;
+        }
+        tuple.end()
+    }
+
+    pub fn deserialize<'de, T, D, const N: usize>(
+        deserializer: D,
+    ) -> Result<[Option<Box<T>>; N], D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(N, ChildrenVisitor(PhantomData))
+    }
+
+    struct ChildrenVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T, const N: usize> Visitor<'de> for ChildrenVisitor<T, N>
+    where
+        T: Deserialize<'de>,
+    {
+        type Value = [Option<Box<T>>; N];
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "an array of {N} children")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut children: Self::Value = core::array::from_fn(|_| None);
+            for (idx, child) in children.iter_mut().enumerate() {
+                *child = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(idx, &self))?;
+            }
+            Ok(children)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Octree;
+    use super::{Direction, Octree, Quadtree};
+    use std::{convert::TryFrom, num::NonZeroU32};
 
     #[test]
     fn test_get_child_out_of_bounds_initial() {
@@ -240,6 +882,19 @@ mod tests {
         assert!(o.get_child_at_pos(false, false, false).is_none());
     }
 
+    #[test]
+    fn test_child_idx_pos_round_trip() {
+        for idx in 0..8 {
+            let (pos_x, pos_y, pos_z) =
+                Octree::<u32>::get_child_pos_at_idx(idx).unwrap();
+            assert_eq!(
+                Octree::<u32>::get_child_idx_at_pos(pos_x, pos_y, pos_z),
+                idx
+            );
+        }
+        assert!(Octree::<u32>::get_child_pos_at_idx(8).is_none());
+    }
+
     #[test]
     fn test_add_child() {
         let mut o = Octree::<Vec<(f32, f32, f32)>>::new();
@@ -254,6 +909,27 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_get_or_create_child_is_idempotent() {
+        let mut o = Octree::<Vec<u32>>::new();
+        o.get_or_create_child(3).unwrap().get_data_mut().push(1);
+        o.get_or_create_child(3).unwrap().get_data_mut().push(2);
+        assert_eq!(o.get_child(3).unwrap().get_data(), &vec![1, 2]);
+        assert!(o.get_or_create_child(8).is_err());
+    }
+
+    #[test]
+    fn test_get_or_create_child_at_pos() {
+        let mut o = Octree::<Vec<u32>>::new();
+        o.get_or_create_child_at_pos(true, false, true)
+            .get_data_mut()
+            .push(1);
+        o.get_or_create_child_at_pos(true, false, true)
+            .get_data_mut()
+            .push(2);
+        assert_eq!(o.get_child(5).unwrap().get_data(), &vec![1, 2]);
+    }
+
     #[test]
     fn test_remove_child() {
         let mut o = Octree::<Vec<(f32, f32, f32)>>::new();
@@ -263,6 +939,152 @@ mod tests {
         assert!(o.get_child(0).is_none());
     }
 
+    #[test]
+    fn test_iter_nodes_depths() {
+        let mut o = Octree::<u32>::new_with_data(0);
+        o.add_child(2, Octree::new_with_data(1))
+            .unwrap()
+            .add_child(5, Octree::new_with_data(3))
+            .unwrap();
+        o.add_child(6, Octree::new_with_data(2)).unwrap();
+        let nodes: Vec<(u32, u32)> = o
+            .iter_nodes()
+            .map(|(node, depth)| (*node.get_data(), depth))
+            .collect();
+        assert_eq!(nodes, vec![(0, 0), (1, 1), (2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn test_iter_nodes_morton_codes() {
+        let mut o = Octree::<u32>::new_with_data(0);
+        o.add_child(2, Octree::new_with_data(1))
+            .unwrap()
+            .add_child(5, Octree::new_with_data(3))
+            .unwrap()
+            .add_child(7, Octree::new_with_data(4))
+            .unwrap();
+        o.add_child(6, Octree::new_with_data(2)).unwrap();
+        let codes: Vec<(u32, u64)> = o
+            .iter_nodes_morton()
+            .map(|(code, node)| (*node.get_data(), code))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![(0, 0), (1, 2), (2, 6), (3, 0b010_101), (4, 0b010_101_111),]
+        );
+    }
+
+    #[test]
+    fn test_clone_is_isolated() {
+        let mut o = Octree::<Vec<u32>>::new_with_data(vec![1]);
+        o.add_child(3, Octree::new_with_data(vec![2])).unwrap();
+        let c = o.clone();
+        o.get_data_mut().push(10);
+        o.get_child_mut(3).unwrap().get_data_mut().push(20);
+        o.add_child(4, Octree::new()).unwrap();
+        assert_eq!(c.get_data(), &vec![1]);
+        assert_eq!(c.get_child(3).unwrap().get_data(), &vec![2]);
+        assert!(c.get_child(4).is_none());
+    }
+
+    #[test]
+    fn test_children_iter() {
+        let mut o = Octree::<usize>::new();
+        o.add_child(5, Octree::new_with_data(5)).unwrap();
+        o.add_child(2, Octree::new_with_data(2)).unwrap();
+        let idxs: Vec<usize> = o.children().map(|(idx, _)| idx).collect();
+        assert_eq!(idxs, vec![2, 5]);
+        for (idx, child) in o.children_mut() {
+            *child.get_data_mut() += idx;
+        }
+        let data: Vec<usize> =
+            o.children().map(|(_, child)| *child.get_data()).collect();
+        assert_eq!(data, vec![4, 10]);
+    }
+
+    #[test]
+    fn test_is_leaf_and_child_count() {
+        let mut o = Octree::<u32>::new();
+        assert!(o.is_leaf());
+        assert_eq!(o.child_count(), 0);
+        o.add_child(1, Octree::new()).unwrap();
+        o.add_child(6, Octree::new()).unwrap();
+        assert!(!o.is_leaf());
+        assert_eq!(o.child_count(), 2);
+        assert!(o.get_child(1).unwrap().is_leaf());
+    }
+
+    #[test]
+    fn test_partial_eq() {
+        let mut o = Octree::<Vec<u32>>::new_with_data(vec![1]);
+        o.add_child(3, Octree::new_with_data(vec![2])).unwrap();
+        let mut c = o.clone();
+        assert_eq!(c, o);
+        c.get_child_mut(3).unwrap().get_data_mut().push(3);
+        assert_ne!(c, o);
+
+        let mut moved = Octree::<Vec<u32>>::new_with_data(vec![1]);
+        moved.add_child(4, Octree::new_with_data(vec![2])).unwrap();
+        assert_ne!(moved, o);
+    }
+
+    #[test]
+    fn test_debug_child_indices() {
+        let mut o = Octree::<u32>::new_with_data(0);
+        o.add_child(2, Octree::new_with_data(1))
+            .unwrap()
+            .add_child(5, Octree::new_with_data(3))
+            .unwrap();
+        o.add_child(6, Octree::new_with_data(2)).unwrap();
+        assert_eq!(format!("{o:?}"), "0\n  [2] 1\n    [5] 3\n  [6] 2");
+    }
+
+    #[test]
+    fn test_debug_depth_elided() {
+        let mut o = Octree::<u32>::new();
+        let mut node = &mut o;
+        for depth in 1..=6 {
+            node = node.add_child(0, Octree::new_with_data(depth)).unwrap();
+        }
+        let bounded = format!("{o:?}");
+        assert!(bounded.contains("[0] 4"));
+        assert!(bounded.contains("[0] ..."));
+        assert!(!bounded.contains("[0] 5"));
+        let full = format!("{o:#?}");
+        assert!(full.contains("[0] 6"));
+        assert!(!full.contains("..."));
+    }
+
+    #[test]
+    fn test_node_and_leaf_count() {
+        let mut o = Octree::<u32>::new();
+        assert_eq!(o.node_count(), 1);
+        assert_eq!(o.leaf_count(), 1);
+        o.add_child(2, Octree::new())
+            .unwrap()
+            .add_child(5, Octree::new())
+            .unwrap();
+        o.add_child(6, Octree::new()).unwrap();
+        assert_eq!(o.node_count(), 4);
+        assert_eq!(o.leaf_count(), 2);
+    }
+
+    #[test]
+    fn test_fold_nodes() {
+        let mut o = Octree::<u32>::new_with_data(1);
+        o.add_child(2, Octree::new_with_data(2))
+            .unwrap()
+            .add_child(5, Octree::new_with_data(3))
+            .unwrap();
+        o.add_child(6, Octree::new_with_data(4)).unwrap();
+        assert_eq!(o.fold_nodes(0, |count, _| count + 1), o.node_count());
+        let order = o.fold_nodes(Vec::new(), |mut order, node| {
+            order.push(*node.get_data());
+            order
+        });
+        assert_eq!(order, vec![1, 2, 3, 4]);
+    }
+
     #[test]
     fn test_remove_child_at_pos() {
         let mut o = Octree::<Vec<(f32, f32, f32)>>::new();
@@ -272,4 +1094,183 @@ mod tests {
         assert!(result.is_some());
         assert!(o.get_child_at_pos(false, false, false).is_none());
     }
+
+    #[test]
+    fn test_get_by_path() {
+        let mut o = Octree::<u32>::new_with_data(0);
+        o.add_child(6, Octree::new_with_data(1))
+            .unwrap()
+            .add_child(6, Octree::new_with_data(2))
+            .unwrap();
+        let chained = o.get_child(6).and_then(|c| c.get_child(6));
+        assert_eq!(o.get_by_path(&[6, 6]), chained);
+        assert_eq!(o.get_by_path(&[6, 6]).map(Octree::get_data), Some(&2));
+        assert_eq!(o.get_by_path(&[]), Some(&o));
+        assert!(o.get_by_path(&[6, 5]).is_none());
+        assert!(o.get_by_path(&[6, 99]).is_none());
+
+        *o.get_by_path_mut(&[6]).unwrap().get_data_mut() = 3;
+        assert_eq!(o.get_child(6).map(Octree::get_data), Some(&3));
+    }
+
+    #[test]
+    fn test_flatten_wiring() {
+        let mut o = Octree::<u32>::new_with_data(0);
+        o.add_child(2, Octree::new_with_data(1))
+            .unwrap()
+            .add_child(5, Octree::new_with_data(3))
+            .unwrap();
+        o.add_child(6, Octree::new_with_data(2)).unwrap();
+        let flat = o.flatten();
+        assert_eq!(flat.len(), o.node_count());
+        let data: Vec<u32> = flat.iter().map(|node| *node.data).collect();
+        assert_eq!(data, vec![0, 1, 2, 3]);
+        assert_eq!(flat[0].parent, None);
+        assert_eq!(flat[0].children[2], Some(1));
+        assert_eq!(flat[0].children[6], Some(2));
+        assert_eq!(flat[1].children[5], Some(3));
+        assert_eq!(flat[3].parent, Some(1));
+        for (idx, node) in (0..).zip(&flat) {
+            for &child in node.children.iter().flatten() {
+                let child = usize::try_from(child).unwrap();
+                assert_eq!(flat[child].parent, Some(idx));
+            }
+        }
+        assert_eq!(
+            flat.iter()
+                .map(|node| node.children.iter().flatten().count())
+                .sum::<usize>(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_map_data_keeps_structure() {
+        let mut o = Octree::<Vec<u32>>::new_with_data(vec![1, 2]);
+        o.add_child(2, Octree::new_with_data(vec![3]))
+            .unwrap()
+            .add_child(5, Octree::new_with_data(vec![4, 5, 6]))
+            .unwrap();
+        o.add_child(6, Octree::new()).unwrap();
+        let counts = o.clone().map_data(|data| data.len());
+        assert_eq!(counts.node_count(), o.node_count());
+        let codes: Vec<u64> = o.iter_nodes_morton().map(|(c, _)| c).collect();
+        let mapped_codes: Vec<u64> =
+            counts.iter_nodes_morton().map(|(c, _)| c).collect();
+        assert_eq!(mapped_codes, codes);
+        let data: Vec<usize> = counts
+            .iter_nodes()
+            .map(|(node, _)| *node.get_data())
+            .collect();
+        assert_eq!(data, vec![2, 1, 0, 3]);
+    }
+
+    fn subdivided_tree(depth: u32) -> Octree<u64> {
+        fn fill(node: &mut Octree<u64>, code: u64, depth: u32) {
+            if depth == 0 {
+                return;
+            }
+            for idx in 0..8 {
+                let child_code = code << 3 | idx as u64;
+                let child = node
+                    .add_child(idx, Octree::new_with_data(child_code))
+                    .unwrap();
+                fill(child, child_code, depth - 1);
+            }
+        }
+        let mut o = Octree::new();
+        fill(&mut o, 0, depth);
+        o
+    }
+
+    #[test]
+    fn test_neighbour_pos_x() {
+        let o = subdivided_tree(2);
+        let code = |path: &[usize], dir| {
+            o.neighbour(path, dir).map(|node| *node.get_data())
+        };
+        assert_eq!(code(&[0, 0], Direction::PosX), Some(0o04));
+        assert_eq!(code(&[0, 4], Direction::PosX), Some(0o40));
+        assert_eq!(code(&[3, 5], Direction::PosX), Some(0o71));
+        assert_eq!(code(&[4, 4], Direction::PosX), None);
+        assert_eq!(code(&[7, 6], Direction::PosX), None);
+        assert_eq!(code(&[6, 3], Direction::NegX), Some(0o27));
+        assert_eq!(code(&[1, 2], Direction::PosY), Some(0o30));
+        assert_eq!(code(&[0, 1], Direction::PosZ), Some(0o10));
+        assert_eq!(code(&[0, 0], Direction::NegZ), None);
+        assert_eq!(code(&[], Direction::PosX), None);
+    }
+
+    #[test]
+    fn test_neighbour_missing_at_depth() {
+        let mut o = subdivided_tree(1);
+        o.get_child_mut(0)
+            .unwrap()
+            .add_child(4, Octree::new_with_data(0o04))
+            .unwrap();
+        assert!(o.neighbour(&[0, 4], Direction::PosX).is_none());
+        assert_eq!(
+            o.neighbour(&[0, 4], Direction::NegX).map(Octree::get_data),
+            None
+        );
+        assert_eq!(
+            o.neighbour(&[0], Direction::PosX).map(Octree::get_data),
+            Some(&0o4)
+        );
+    }
+
+    #[test]
+    fn test_non_default_data() {
+        let id = |n| NonZeroU32::new(n).unwrap();
+        let mut o = Octree::new_with_data(id(1));
+        o.add_child(3, Octree::new_with_data(id(2))).unwrap();
+        o.get_child_mut(3)
+            .unwrap()
+            .add_child(5, Octree::new_with_data(id(3)))
+            .unwrap();
+        assert_eq!(*o.get_data(), id(1));
+        assert_eq!(*o.get_by_path(&[3, 5]).unwrap().get_data(), id(3));
+        *o.get_child_mut(3).unwrap().get_data_mut() = id(4);
+        assert_eq!(o.get_child(3).unwrap().get_data().get(), 4);
+        assert_eq!(o.clone(), o);
+        assert_eq!(format!("{o:?}"), "1\n  [3] 4\n    [5] 3");
+
+        let sum = o.fold_nodes(0, |acc, node| acc + node.get_data().get());
+        assert_eq!(sum, 8);
+        let mut doubled = o.map_data(|n| id(n.get() * 2));
+        assert_eq!(doubled.get_child(3).unwrap().get_data().get(), 8);
+        assert_eq!(doubled.remove_child(3).map(|c| c.node_count()), Some(2));
+        assert!(doubled.is_leaf());
+    }
+
+    #[test]
+    fn test_quadtree_shared_machinery() {
+        let mut q = Quadtree::<Vec<(i32, i32)>>::new();
+        for &(x, y) in &[(1, 2), (-3, 4), (5, -6), (7, 8)] {
+            q.get_or_create_child_at_pos(x > 0, y > 0)
+                .get_data_mut()
+                .push((x, y));
+        }
+        assert_eq!(q.child_count(), 3);
+        assert_eq!(
+            q.get_child_at_pos(true, true).unwrap().get_data(),
+            &vec![(1, 2), (7, 8)]
+        );
+        assert!(q.get_child(4).is_none());
+        assert!(q.add_child(4, Quadtree::new()).is_err());
+        for idx in 0..4 {
+            let (x, y) = Quadtree::<()>::get_child_pos_at_idx(idx).unwrap();
+            assert_eq!(Quadtree::<()>::get_child_idx_at_pos(x, y), idx);
+        }
+
+        q.get_or_create_child_at_pos(true, true)
+            .get_or_create_child_at_pos(false, true);
+        let codes: Vec<u64> = q.iter_nodes_morton().map(|(c, _)| c).collect();
+        assert_eq!(codes, vec![0, 1, 2, 3, 3 << 2 | 1]);
+        assert_eq!(q.flatten()[0].children, [None, Some(1), Some(2), Some(3)]);
+        assert_eq!(q.node_count(), 5);
+
+        let counts = q.map_data(|d| d.len());
+        assert_eq!(counts.fold_nodes(0, |acc, n| acc + n.get_data()), 4);
+    }
 }
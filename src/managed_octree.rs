@@ -1,25 +1,281 @@
-use super::Octree;
+mod builder;
+#[cfg(feature = "std")]
+mod entry;
+mod iter;
+mod mass;
+mod metric;
+#[cfg(feature = "rayon")]
+mod par;
+mod query;
+mod validate;
+
+pub use builder::{BuildError, OctreeBuilder, OctreeConfig};
+#[cfg(feature = "std")]
+pub use entry::{Entry, VacantEntry};
+pub use iter::ManagedOctreeIntoIter;
+pub use mass::{MassDistribution, Weighted};
+pub use metric::{Chebyshev, Euclidean, Manhattan, Metric};
+pub use query::Axis;
+pub use validate::InvariantError;
+
+use super::{AddChildError, Octree};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, btree_map},
+    vec::Vec,
+};
+use core::{
+    borrow::{Borrow, BorrowMut},
+    iter::FromIterator,
+    mem,
+    ops::{Add, Div, Mul, Sub},
+    slice,
+};
 use len_trait::{Clear, Empty, Len};
 use num::One;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use std::{
-    borrow::{Borrow, BorrowMut},
-    collections::HashMap,
-    hash::Hash,
-    mem,
-    ops::{Add, Div, Sub},
+    collections::{HashMap, hash_map},
+    hash::{BuildHasher, Hash},
 };
 
-pub type ManagedOctree<D, S> = Octree<ManagedOctreeData<D, S>>;
-pub type ManagedVecOctree<T, S> = ManagedOctree<Vec<T>, S>;
-pub type ManagedHashMapOctree<K, V, S> = ManagedOctree<HashMap<K, V>, S>;
+/// Gets the index of the child that `point` belongs to in a node centred on
+/// `centre`, as used to place items in a managed octree.
+///
+/// A point goes to the positive side of an axis only if it is strictly greater
+/// than the centre, so points lying exactly on a split plane belong to the
+/// negative side.
+pub fn octant_index<S>(
+    (px, py, pz): (S, S, S),
+    (cx, cy, cz): (S, S, S),
+) -> usize
+where
+    S: PartialOrd,
+{
+    Octree::<()>::get_child_idx_at_pos(px > cx, py > cy, pz > cz)
+}
+
+/// Gets the centre and half length along one axis of the child on the given
+/// side of `split`, within a node with centre `c` and half length `h`. As with
+/// midpoint splits, integer half lengths are rounded up to keep the children
+/// covering the node.
+fn split_child_axis<S>(c: S, h: S, split: S, pos: bool) -> (S, S)
+where
+    S: Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+{
+    let (lo, hi) = if pos { (split, c + h) } else { (c - h, split) };
+    let two = S::one() + S::one();
+    let q = (hi - lo) / two;
+    (lo + q, hi - lo - q)
+}
+
+/// Adds `n` items to a node's count of the items in its subtree.
+///
+/// # Panics
+/// Panics if the count overflows, as it can only do so if it's wrong.
+fn grow_len(len: &mut usize, n: usize) {
+    *len = len.checked_add(n).unwrap_or_else(|| {
+        panic!("octree len overflowed adding {} to {}", n, len)
+    });
+}
+
+/// Removes `n` items from a node's count of the items in its subtree.
+///
+/// # Panics
+/// Panics if the count would drop below zero, which means it had fallen out
+/// of step with the items actually held, rather than wrapping around to a
+/// huge value.
+fn shrink_len(len: &mut usize, n: usize) {
+    *len = len.checked_sub(n).unwrap_or_else(|| {
+        panic!("octree len underflowed removing {} from {}", n, len)
+    });
+}
+
+/// Clamps a centroid to within a node with centre `c` and half length `h` on
+/// one axis, returning it along with whether the children split there would
+/// still cover the node once rounded.
+fn centroid_axis<S>(c: S, h: S, mean: S) -> (S, bool)
+where
+    S: Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+{
+    let (lo, hi) = (c - h, c + h);
+    let split = if mean < lo {
+        lo
+    } else if mean > hi {
+        hi
+    } else {
+        mean
+    };
+    let (nc, nh) = split_child_axis(c, h, split, false);
+    let (pc, ph) = split_child_axis(c, h, split, true);
+    let covers =
+        nc - nh <= lo && nc + nh >= split && pc - ph <= split && pc + ph >= hi;
+    (split, covers)
+}
+
+/// Gets the centre of the box spanning `min` to `max`, along with the half
+/// lengths needed to reach both corners from it. These can be larger than half
+/// of the box's extent when `S` is an integer type, as the centre is rounded.
+fn aabb_centre_and_half_lengths<S>(
+    min: (S, S, S),
+    max: (S, S, S),
+) -> ((S, S, S), (S, S, S))
+where
+    S: Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+{
+    let two = S::one() + S::one();
+    let covering = |min: S, max: S| {
+        let c = (min + max) / two;
+        if max - c > c - min {
+            (c, max - c)
+        } else {
+            (c, c - min)
+        }
+    };
+    let (cx, hx) = covering(min.0, max.0);
+    let (cy, hy) = covering(min.1, max.1);
+    let (cz, hz) = covering(min.2, max.2);
+    ((cx, cy, cz), (hx, hy, hz))
+}
+
+/// The minimum and maximum corners of an axis-aligned box.
+type Bounds<S> = ((S, S, S), (S, S, S));
+
+/// A leaf's centre and half lengths along with its data, as yielded by
+/// `leaves`.
+type Leaf<'a, S, D> = ((S, S, S), (S, S, S), &'a D);
+
+/// The centre and half lengths of each of a node's eight children.
+type ChildBounds<S> = [((S, S, S), (S, S, S)); 8];
+
+/// Gets the minimum and maximum corners of the smallest box containing every
+/// point, or `None` if there are none.
+fn point_bounds<S>(
+    mut points: impl Iterator<Item = (S, S, S)>,
+) -> Option<Bounds<S>>
+where
+    S: Copy + PartialOrd,
+{
+    let first = points.next()?;
+    Some(points.fold((first, first), |(min, max), p| {
+        (
+            (
+                if p.0 < min.0 { p.0 } else { min.0 },
+                if p.1 < min.1 { p.1 } else { min.1 },
+                if p.2 < min.2 { p.2 } else { min.2 },
+            ),
+            (
+                if p.0 > max.0 { p.0 } else { max.0 },
+                if p.1 > max.1 { p.1 } else { max.1 },
+                if p.2 > max.2 { p.2 } else { max.2 },
+            ),
+        )
+    }))
+}
+
+pub type ManagedOctree<D, S, M = ()> = Octree<ManagedOctreeData<D, S, M>>;
+pub type ManagedVecOctree<T, S, M = ()> = ManagedOctree<Vec<T>, S, M>;
+#[cfg(feature = "std")]
+pub type ManagedHashMapOctree<K, V, S, M = ()> =
+    ManagedOctree<HashMap<K, V>, S, M>;
+pub type ManagedBTreeMapOctree<K, V, S, M = ()> =
+    ManagedOctree<BTreeMap<K, V>, S, M>;
 
 /// A trait that will allow the underlying collection to be treated generically.
 pub trait OctreeCollection<I> {
     fn add(&mut self, item: I) -> Option<()>;
+
+    /// Checks whether `add` would accept `item`, so that a rejected item can be
+    /// kept elsewhere rather than dropped. Collections accepting every item
+    /// needn't override this.
+    fn accepts(&self, _item: &I) -> bool { true }
+}
+
+/// A trait that allows the items of the underlying collection to be visited by
+/// reference, e.g. when querying the tree.
+pub trait OctreeCollectionRef<S> {
+    type ItemRef<'a>: CentredItem<S>
+    where
+        Self: 'a;
+    type Iter<'a>: Iterator<Item = Self::ItemRef<'a>>
+    where
+        Self: 'a;
+
+    fn iter_items(&self) -> Self::Iter<'_>;
+
+    /// Checks whether this collection would accept the item behind `item`, as
+    /// `OctreeCollection::accepts` does for an owned item.
+    fn accepts_ref<'a>(&'a self, _item: &Self::ItemRef<'a>) -> bool { true }
+}
+
+/// A trait giving a rough estimate of the heap memory used by the underlying
+/// collection, for use by `approx_size_bytes`.
+pub trait OctreeCollectionSize {
+    fn heap_size_bytes(&self) -> usize;
+}
+
+impl<I> OctreeCollectionSize for Vec<I> {
+    fn heap_size_bytes(&self) -> usize { self.capacity() * mem::size_of::<I>() }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, H> OctreeCollectionSize for HashMap<K, V, H> {
+    /// Counts the space reserved for entries, ignoring the map's control bytes.
+    fn heap_size_bytes(&self) -> usize {
+        self.capacity() * mem::size_of::<(K, V)>()
+    }
+}
+
+impl<K, V> OctreeCollectionSize for BTreeMap<K, V> {
+    /// Counts the entries alone, ignoring the tree's node overhead.
+    fn heap_size_bytes(&self) -> usize { self.len() * mem::size_of::<(K, V)>() }
 }
 
+/// A trait for items that can be stored in a managed octree, which are placed
+/// according to their centre. Implement this for custom types (e.g. a struct
+/// with a position field) to store them directly.
 pub trait CentredItem<S> {
     fn centre(&self) -> (S, S, S);
+
+    /// Gets the item's half extent along each axis, which is zero for points.
+    /// Items with extent are only moved into a child whose loose bounds (see
+    /// `with_looseness`) fully contain them.
+    fn half_extents(&self) -> (S, S, S)
+    where
+        S: Default,
+    {
+        Default::default()
+    }
+}
+
+impl<S, T> CentredItem<S> for &T
+where
+    T: CentredItem<S> + ?Sized,
+{
+    fn centre(&self) -> (S, S, S) { (**self).centre() }
+
+    fn half_extents(&self) -> (S, S, S)
+    where
+        S: Default,
+    {
+        (**self).half_extents()
+    }
 }
 
 impl<S> CentredItem<S> for (S, S, S)
@@ -29,6 +285,13 @@ where
     fn centre(&self) -> (S, S, S) { *self }
 }
 
+impl<S> CentredItem<S> for [S; 3]
+where
+    S: Copy,
+{
+    fn centre(&self) -> (S, S, S) { (self[0], self[1], self[2]) }
+}
+
 impl<S, K> CentredItem<S> for (K, (S, S, S))
 where
     S: Copy,
@@ -36,6 +299,40 @@ where
     fn centre(&self) -> (S, S, S) { self.1 }
 }
 
+impl<S, K> CentredItem<S> for (&K, &(S, S, S))
+where
+    S: Copy,
+{
+    fn centre(&self) -> (S, S, S) { *self.1 }
+}
+
+/// A trait for items whose centre can be changed in place, allowing them to be
+/// moved within a tree with `update_position`.
+pub trait MovableItem<S>: CentredItem<S> {
+    fn set_centre(&mut self, centre: (S, S, S));
+}
+
+impl<S> MovableItem<S> for (S, S, S)
+where
+    S: Copy,
+{
+    fn set_centre(&mut self, centre: (S, S, S)) { *self = centre; }
+}
+
+impl<S> MovableItem<S> for [S; 3]
+where
+    S: Copy,
+{
+    fn set_centre(&mut self, centre: (S, S, S)) { *self = centre.into(); }
+}
+
+impl<S, K> MovableItem<S> for (K, (S, S, S))
+where
+    S: Copy,
+{
+    fn set_centre(&mut self, centre: (S, S, S)) { self.1 = centre; }
+}
+
 impl<I> OctreeCollection<I> for Vec<I> {
     fn add(&mut self, item: I) -> Option<()> {
         self.push(item);
@@ -43,9 +340,26 @@ impl<I> OctreeCollection<I> for Vec<I> {
     }
 }
 
-impl<K, V> OctreeCollection<(K, V)> for HashMap<K, V>
+#[cfg(feature = "std")]
+impl<K, V, H> OctreeCollection<(K, V)> for HashMap<K, V, H>
 where
     K: Eq + Hash,
+    H: BuildHasher,
+{
+    fn add(&mut self, (key, val): (K, V)) -> Option<()> {
+        if self.contains_key(&key) {
+            return None;
+        }
+        self.insert(key, val);
+        Some(())
+    }
+
+    fn accepts(&self, (key, _): &(K, V)) -> bool { !self.contains_key(key) }
+}
+
+impl<K, V> OctreeCollection<(K, V)> for BTreeMap<K, V>
+where
+    K: Ord,
 {
     fn add(&mut self, (key, val): (K, V)) -> Option<()> {
         if self.contains_key(&key) {
@@ -54,22 +368,132 @@ where
         self.insert(key, val);
         Some(())
     }
+
+    fn accepts(&self, (key, _): &(K, V)) -> bool { !self.contains_key(key) }
+}
+
+impl<S, I> OctreeCollectionRef<S> for Vec<I>
+where
+    I: CentredItem<S>,
+{
+    type ItemRef<'a>
+        = &'a I
+    where
+        Self: 'a;
+    type Iter<'a>
+        = slice::Iter<'a, I>
+    where
+        Self: 'a;
+
+    fn iter_items(&self) -> Self::Iter<'_> { self.iter() }
+}
+
+#[cfg(feature = "std")]
+impl<S, K, V, H> OctreeCollectionRef<S> for HashMap<K, V, H>
+where
+    for<'a> (&'a K, &'a V): CentredItem<S>,
+    K: Eq + Hash,
+    H: BuildHasher,
+{
+    type ItemRef<'a>
+        = (&'a K, &'a V)
+    where
+        Self: 'a;
+    type Iter<'a>
+        = hash_map::Iter<'a, K, V>
+    where
+        Self: 'a;
+
+    fn iter_items(&self) -> Self::Iter<'_> { self.iter() }
+
+    fn accepts_ref<'a>(&'a self, (key, _): &Self::ItemRef<'a>) -> bool {
+        !self.contains_key(*key)
+    }
+}
+
+impl<S, K, V> OctreeCollectionRef<S> for BTreeMap<K, V>
+where
+    for<'a> (&'a K, &'a V): CentredItem<S>,
+    K: Ord,
+{
+    type ItemRef<'a>
+        = (&'a K, &'a V)
+    where
+        Self: 'a;
+    type Iter<'a>
+        = btree_map::Iter<'a, K, V>
+    where
+        Self: 'a;
+
+    fn iter_items(&self) -> Self::Iter<'_> { self.iter() }
+
+    fn accepts_ref<'a>(&'a self, (key, _): &Self::ItemRef<'a>) -> bool {
+        !self.contains_key(*key)
+    }
+}
+
+/// Returned when an item lies outside of the bounds of the node it is being
+/// added to.
+#[derive(Debug)]
+pub struct OutOfBoundsError<S> {
+    pub point: (S, S, S),
+    pub centre: (S, S, S),
+    pub half_lengths: (S, S, S),
+}
+
+/// Returned when merging trees whose root bounds differ, handing back the tree
+/// that couldn't be merged.
+#[derive(Debug)]
+pub struct BoundsMismatchError<D, S, M = ()>
+where
+    D: Default + Empty + Len,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    pub other: ManagedOctree<D, S, M>,
+}
+
+/// Where `rebalance` places the planes dividing a node into its children.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SplitStrategy {
+    /// Split through the centre of the node, giving eight equal children.
+    #[default]
+    Midpoint,
+    /// Split through the mean of the centres of the node's items on each
+    /// axis, giving unequal children that follow the items. This keeps trees
+    /// over clustered data shallower than splitting at the midpoint would.
+    Centroid,
 }
 
-pub struct ManagedOctreeData<D, S>
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ManagedOctreeData<D, S, M = ()>
 where
     D: Default + Empty + Len,
     S: Default + One,
 {
     centre: (S, S, S),
-    half_length: S,
+    half_lengths: (S, S, S),
     max_size: usize,
+    split_threshold: usize,
     drop_below_size: usize,
+    max_depth: u32,
+    looseness: S,
+    split_strategy: SplitStrategy,
+    /// The point the children were split around, if not `centre`.
+    split: Option<(S, S, S)>,
     len: usize,
+    meta: M,
     data: D,
 }
 
-impl<D, S> Default for ManagedOctreeData<D, S>
+impl<D, S, M> Default for ManagedOctreeData<D, S, M>
 where
     D: Default + Empty + Len,
     S: Default
@@ -78,20 +502,27 @@ where
         + Add<S, Output = S>
         + Sub<S, Output = S>
         + Div<S, Output = S>,
+    M: Default,
 {
     fn default() -> Self {
         Self {
             centre: (S::default(), S::default(), S::default()),
-            half_length: S::one(),
+            half_lengths: (S::one(), S::one(), S::one()),
             max_size: 1,
+            split_threshold: 0,
             drop_below_size: 1,
+            max_depth: u32::MAX,
+            looseness: S::default(),
+            split_strategy: SplitStrategy::Midpoint,
+            split: None,
             len: 0,
+            meta: M::default(),
             data: D::default(),
         }
     }
 }
 
-impl<D, S> ManagedOctreeData<D, S>
+impl<D, S, M> ManagedOctreeData<D, S, M>
 where
     D: Default + Empty + Len,
     S: Default
@@ -100,6 +531,7 @@ where
         + Add<S, Output = S>
         + Sub<S, Output = S>
         + Div<S, Output = S>,
+    M: Default,
 {
     /// Gets a reference to the underlying data in the node.
     #[must_use]
@@ -110,313 +542,3412 @@ where
     pub fn get_data_mut(&mut self) -> &mut D { self.data.borrow_mut() }
 }
 
-impl<D, S, T> ManagedOctree<D, S>
+impl<D, S, M> ManagedOctree<D, S, M>
 where
-    D: Default
-        + Empty
-        + Len
-        + Clear
-        + IntoIterator<Item = T>
-        + OctreeCollection<T>,
-    T: CentredItem<S>,
+    D: Default + Empty + Len,
     S: Default
         + Copy
         + One
-        + PartialOrd
         + Add<S, Output = S>
         + Sub<S, Output = S>
         + Div<S, Output = S>,
+    M: Default,
 {
+    /// Gets the centre of the node.
     #[must_use]
-    pub fn new_managed(centre: (S, S, S), half_length: S) -> Self {
-        Self::new_with_data(ManagedOctreeData {
-            centre,
-            half_length,
-            ..ManagedOctreeData::default()
-        })
-    }
+    pub const fn centre(&self) -> (S, S, S) { self.data.centre }
 
-    /// Set `max_size`
+    /// Gets the node's half length along each axis.
     #[must_use]
-    pub fn with_max_size(mut self, max_size: usize) -> Self {
-        self.data.max_size = max_size;
-        self
+    pub const fn half_lengths(&self) -> (S, S, S) { self.data.half_lengths }
+
+    /// Gets the point where this node's split planes meet, which is its centre
+    /// unless it was split with `SplitStrategy::Centroid`. Use this rather
+    /// than `centre` with `octant_index` to find the child a point belongs to.
+    #[must_use]
+    pub const fn split_point(&self) -> (S, S, S) {
+        match self.data.split {
+            Some(split) => split,
+            None => self.data.centre,
+        }
     }
 
-    /// Set `drop_below_size`
-    ///
-    /// Panics when set to 0
+    /// Gets the number of items `rebalance` leaves in a node when splitting it.
+    /// Unless raised with `with_split_threshold`, this is also the number of
+    /// items a node may hold before it is split.
     #[must_use]
-    pub fn with_drop_below_size(mut self, drop_below_size: usize) -> Self {
-        if drop_below_size == 0 {
-            panic!("drop_below_size must be greater than 0");
+    pub const fn max_size(&self) -> usize { self.data.max_size }
+
+    /// Gets the number of items a node may hold before `rebalance` splits it,
+    /// which is never less than `max_size`.
+    #[must_use]
+    pub const fn split_threshold(&self) -> usize {
+        if self.data.split_threshold > self.data.max_size {
+            self.data.split_threshold
+        } else {
+            self.data.max_size
         }
+    }
 
-        self.data.drop_below_size = drop_below_size;
-        self
+    /// Checks whether this node's own buffer holds more items than its split
+    /// threshold.
+    fn over_split_threshold(&self) -> bool {
+        self.data.data.len() > self.split_threshold()
     }
 
-    /// Adds data to the node without flushing/rebalancing the tree.
-    pub fn add(&mut self, item: T) {
-        self.data.data.add(item);
-        self.data.len += 1;
+    /// Gets the item count below which `collapse` merges a node's children
+    /// back into it.
+    #[must_use]
+    pub const fn drop_below_size(&self) -> usize { self.data.drop_below_size }
+
+    /// Gets the number of levels of children that `rebalance` may create below
+    /// this node.
+    #[must_use]
+    pub const fn max_depth(&self) -> u32 { self.data.max_depth }
+
+    /// Gets the factor by which children's bounds are expanded when placing
+    /// items with extent. See `with_looseness`.
+    #[must_use]
+    pub const fn looseness(&self) -> S { self.data.looseness }
+
+    /// Gets the minimum and maximum corners of the node.
+    #[must_use]
+    pub fn bounds(&self) -> ((S, S, S), (S, S, S)) {
+        let (cx, cy, cz) = self.data.centre;
+        let (hx, hy, hz) = self.data.half_lengths;
+        ((cx - hx, cy - hy, cz - hz), (cx + hx, cy + hy, cz + hz))
     }
 
-    /// Clears data from the node (not the whole tree)
-    pub fn clear_data(&mut self) {
-        self.data.len -= self.data.data.len();
-        self.data.data.clear()
+    /// Iterates over the leaves in this subtree, i.e. the nodes without
+    /// children, yielding the centre, half lengths and data of each. Leaves are
+    /// visited breadth-first, as in `iter_nodes`.
+    pub fn leaves(&self) -> impl Iterator<Item = Leaf<'_, S, D>> {
+        self.iter_nodes()
+            .filter(|(node, _)| node.is_leaf())
+            .map(|(node, _)| {
+                (node.data.centre, node.data.half_lengths, &node.data.data)
+            })
     }
 
-    pub fn rebalance(&mut self) {
-        let bucket_counts = self.move_to_existing_children();
-        if self.data.data.len() <= self.data.max_size {
-            return;
-        }
-        let bucket_sizes = Self::sort_bucket_sizes(bucket_counts);
-        let mut new_size = self.data.data.len();
-        for (max_idx, max_val) in bucket_sizes {
-            let (px, py, pz) = Self::get_child_pos_at_idx(max_idx);
-            let (centre, half_length) =
-                self.get_child_centre_and_half_length_at_pos(px, py, pz);
-            self.add_child(
-                max_idx,
-                Self::new_managed(centre, half_length)
-                    .with_max_size(self.data.max_size)
-                    .with_drop_below_size(self.data.drop_below_size),
-            )
-            .unwrap();
-            new_size -= max_val;
-            if new_size <= self.data.max_size {
-                break;
-            }
+    /// Checks whether this node's own buffer holds no items, ignoring its
+    /// children. This differs from `is_empty`, which checks the whole subtree,
+    /// as an internal node's items may all have been moved into its children.
+    #[must_use]
+    pub fn is_node_empty(&self) -> bool { self.data.data.is_empty() }
+
+    /// Gets this node's own buffer, or `None` if it holds no items (see
+    /// `is_node_empty`).
+    #[must_use]
+    pub fn data_if_nonempty(&self) -> Option<&D> {
+        if self.is_node_empty() {
+            None
+        } else {
+            Some(&self.data.data)
         }
-        self.move_to_existing_children();
     }
 
-    fn sort_bucket_sizes(sizes: [usize; 8]) -> Vec<(usize, usize)> {
-        let mut bucket_sizes: Vec<(usize, usize)> =
-            sizes.iter().enumerate().map(|(i, &v)| (i, v)).collect();
-        bucket_sizes.sort_unstable_by(|(_ai, am), (_bi, bm)| {
-            bm.partial_cmp(am).unwrap()
-        });
-        bucket_sizes
+    /// Removes the descendant at `path` (see `get_by_path`) along with its
+    /// subtree, subtracting the items it held from the `len` of every node
+    /// above it. Returns `None`, leaving the tree unchanged, if `path` is empty
+    /// or leads to a missing child.
+    pub fn remove_subtree(&mut self, path: &[usize]) -> Option<Self> {
+        let (&idx, rest) = path.split_first()?;
+        let removed = if rest.is_empty() {
+            self.remove_child(idx)?
+        } else {
+            self.get_child_mut(idx)?.remove_subtree(rest)?
+        };
+        shrink_len(&mut self.data.len, removed.data.len);
+        Some(removed)
     }
 
-    /// Moves any objects that should belong to a child to that child if it
-    /// exists. Returns the bucket sizes of any remaining items.
-    fn move_to_existing_children(&mut self) -> [usize; 8] {
-        let (cx, cy, cz) = self.data.centre;
+    /// Removes child `idx` and returns it as a standalone tree with its own
+    /// bounds, configuration and items, subtracting the items it held from
+    /// this node's `len`. Unlike `remove_child`, the `len` of both trees stays
+    /// consistent, so either can be queried or rebalanced afterwards.
+    pub fn take_subtree(&mut self, idx: usize) -> Option<Self> {
+        self.remove_subtree(&[idx])
+    }
 
-        let mut result = [0; 8];
-        let mut old_d = D::default();
-        mem::swap(&mut old_d, &mut self.data.data);
-        for item in old_d {
-            let (ix, iy, iz) = item.centre();
-            let idx = Self::get_child_idx_at_pos(ix > cx, iy > cy, iz > cz);
-            if let Some(child) = &mut self.children[idx] {
-                child.add(item);
-            } else {
-                self.add(item);
-                result[idx] += 1;
+    /// Removes every child subtree that holds no items, returning the number
+    /// of nodes removed. Unlike `collapse`, no items are moved, so children
+    /// holding any items are kept however few they hold.
+    pub fn prune_empty(&mut self) -> usize {
+        let mut removed = 0;
+        for slot in &mut self.children {
+            if let Some(child) = slot {
+                if child.data.len == 0 {
+                    removed += child.node_count();
+                    *slot = None;
+                } else {
+                    removed += child.prune_empty();
+                }
             }
         }
-
-        result
+        removed
     }
 
-    fn get_child_centre_and_half_length_at_pos(
-        &self,
-        pos_x: bool,
-        pos_y: bool,
-        pos_z: bool,
-    ) -> ((S, S, S), S) {
-        let (cx, cy, cz) = self.data.centre;
-        let hhl = self.data.half_length / (S::one() + S::one());
-        match (pos_x, pos_y, pos_z) {
-            (false, false, false) => ((cx - hhl, cy - hhl, cz - hhl), (hhl)),
-            (false, false, true) => ((cx - hhl, cy - hhl, cz + hhl), (hhl)),
-            (false, true, false) => ((cx - hhl, cy + hhl, cz - hhl), (hhl)),
-            (false, true, true) => ((cx - hhl, cy + hhl, cz + hhl), (hhl)),
-            (true, false, false) => ((cx + hhl, cy - hhl, cz - hhl), (hhl)),
-            (true, false, true) => ((cx + hhl, cy - hhl, cz + hhl), (hhl)),
-            (true, true, false) => ((cx + hhl, cy + hhl, cz - hhl), (hhl)),
-            (true, true, true) => ((cx + hhl, cy + hhl, cz + hhl), (hhl)),
-        }
+    /// Adds `subtree` as the descendant at `path` (see `get_by_path`), adding
+    /// the items it holds to the `len` of every node above it. This is the
+    /// inverse of `remove_subtree`. The subtree is added as is, so its bounds
+    /// should match those of the octant it's added to.
+    ///
+    /// # Errors
+    /// Returns an error, leaving the tree unchanged, if `path` is empty or
+    /// leads through a missing child (`MissingParent`), if an index is out of
+    /// range, or if the final child is already present.
+    pub fn add_subtree(
+        &mut self,
+        path: &[usize],
+        subtree: Self,
+    ) -> Result<&mut Self, AddChildError> {
+        let (&idx, rest) =
+            path.split_first().ok_or(AddChildError::MissingParent)?;
+        let added = subtree.data.len;
+        let Self { children, data } = self;
+        let slot =
+            children.get_mut(idx).ok_or(AddChildError::OutOfBoundsIdx)?;
+        let node: &mut Self = if rest.is_empty() {
+            if slot.is_some() {
+                return Err(AddChildError::AlreadyAdded);
+            }
+            slot.insert(Box::new(subtree))
+        } else {
+            slot.as_deref_mut()
+                .ok_or(AddChildError::MissingParent)?
+                .add_subtree(rest, subtree)?
+        };
+        grow_len(&mut data.len, added);
+        Ok(node)
     }
-}
 
-impl<T, S> Empty for ManagedVecOctree<T, S>
-where
-    S: Default
-        + Copy
-        + One
-        + Add<S, Output = S>
-        + Sub<S, Output = S>
-        + Div<S, Output = S>,
-{
-    fn is_empty(&self) -> bool { self.data.len == 0 }
+    /// Gets the user metadata attached to this node, which is `M::default()`
+    /// until set with `meta_mut` or `recompute_meta`.
+    #[must_use]
+    pub const fn meta(&self) -> &M { &self.data.meta }
+
+    /// Gets a mutable reference to the user metadata attached to this node.
+    #[must_use]
+    pub const fn meta_mut(&mut self) -> &mut M { &mut self.data.meta }
+
+    /// Gets the maximum depth of any node in the tree, relative to this node
+    /// (which has a depth of 0).
+    #[must_use]
+    pub fn depth(&self) -> u32 {
+        self.children
+            .iter()
+            .flatten()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
 }
 
-impl<T, S> Len for ManagedVecOctree<T, S>
+impl<D, S, M> ManagedOctree<D, S, M>
 where
+    D: Default + Empty + Len + OctreeCollectionSize,
     S: Default
         + Copy
         + One
         + Add<S, Output = S>
         + Sub<S, Output = S>
         + Div<S, Output = S>,
+    M: Default,
 {
-    fn len(&self) -> usize { self.data.len }
+    /// Estimates the memory used by this node and all of its descendants,
+    /// counting each node along with the heap memory reported by its
+    /// collection. Items' own heap allocations aren't included.
+    #[must_use]
+    pub fn approx_size_bytes(&self) -> usize {
+        self.fold_nodes(0, |size, node| {
+            size + mem::size_of::<Self>() + node.data.data.heap_size_bytes()
+        })
+    }
 }
 
-impl<K, V, S> Empty for ManagedHashMapOctree<K, V, S>
+impl<D, S, M> ManagedOctree<D, S, M>
 where
-    K: Eq + Hash,
+    D: Default + Empty + Len,
     S: Default
         + Copy
         + One
+        + PartialOrd
         + Add<S, Output = S>
         + Sub<S, Output = S>
         + Div<S, Output = S>,
+    M: Default,
 {
-    fn is_empty(&self) -> bool { self.data.len == 0 }
-}
+    /// Checks whether a point lies within the node's bounds.
+    ///
+    /// Matching the rule used to place items in children, where points lying
+    /// exactly on a split plane belong to the negative side, the bounds exclude
+    /// their lower faces and include their upper faces.
+    #[must_use]
+    pub fn contains_point(&self, (px, py, pz): (S, S, S)) -> bool {
+        let (cx, cy, cz) = self.data.centre;
+        let (hx, hy, hz) = self.data.half_lengths;
+        cx - hx < px
+            && px <= cx + hx
+            && cy - hy < py
+            && py <= cy + hy
+            && cz - hz < pz
+            && pz <= cz + hz
+    }
 
-impl<K, V, S> Len for ManagedHashMapOctree<K, V, S>
+    /// Gets the distance from `point` to the nearest face of the node's bounds.
+    ///
+    /// This is positive inside the node, and zero on its faces. Outside the
+    /// node it is negative, with the magnitude being how far the point lies
+    /// past the faces along the axis where it is furthest out, i.e. the
+    /// Chebyshev rather than Euclidean distance to the box.
+    #[must_use]
+    pub fn distance_to_bounds(&self, (px, py, pz): (S, S, S)) -> S {
+        let (cx, cy, cz) = self.data.centre;
+        let (hx, hy, hz) = self.data.half_lengths;
+        let inset = |p: S, c: S, h: S| {
+            if p > c { c + h - p } else { p - (c - h) }
+        };
+        let (dx, dy, dz) =
+            (inset(px, cx, hx), inset(py, cy, hy), inset(pz, cz, hz));
+        let min = if dx < dy { dx } else { dy };
+        if dz < min { dz } else { min }
+    }
+
+    /// Gets the index of the child that a point belongs to. Points lying
+    /// exactly on a split plane belong to the negative side.
+    fn get_child_idx_for_point(&self, point: (S, S, S)) -> usize {
+        octant_index(point, self.split_point())
+    }
+
+    /// Gets the centre and per-axis half lengths of a child, halving each axis
+    /// of this node independently. For integer `S` the children overlap by up
+    /// to one unit along each axis that has an odd half length.
+    ///
+    /// If the node was split away from its centre, each child instead spans
+    /// from the split point to the node's face on each axis.
+    fn get_child_centre_and_half_length_at_pos(
+        &self,
+        pos_x: bool,
+        pos_y: bool,
+        pos_z: bool,
+    ) -> ((S, S, S), (S, S, S)) {
+        if let Some((sx, sy, sz)) = self.data.split {
+            let (cx, cy, cz) = self.data.centre;
+            let (hx, hy, hz) = self.data.half_lengths;
+            let (x, hx) = split_child_axis(cx, hx, sx, pos_x);
+            let (y, hy) = split_child_axis(cy, hy, sy, pos_y);
+            let (z, hz) = split_child_axis(cz, hz, sz, pos_z);
+            return ((x, y, z), (hx, hy, hz));
+        }
+        let (cx, cy, cz) = self.data.centre;
+        let two = S::one() + S::one();
+        let (hx, hy, hz) = self.data.half_lengths;
+        // Rounding the half lengths up rather than down keeps integer children
+        // covering the whole of this node, otherwise items on its faces would
+        // lie outside of the child they're placed in and be missed by queries.
+        let hhl = (hx - hx / two, hy - hy / two, hz - hz / two);
+        let (hx, hy, hz) = (hx / two, hy / two, hz / two);
+        match (pos_x, pos_y, pos_z) {
+            (false, false, false) => ((cx - hx, cy - hy, cz - hz), hhl),
+            (false, false, true) => ((cx - hx, cy - hy, cz + hz), hhl),
+            (false, true, false) => ((cx - hx, cy + hy, cz - hz), hhl),
+            (false, true, true) => ((cx - hx, cy + hy, cz + hz), hhl),
+            (true, false, false) => ((cx + hx, cy - hy, cz - hz), hhl),
+            (true, false, true) => ((cx + hx, cy - hy, cz + hz), hhl),
+            (true, true, false) => ((cx + hx, cy + hy, cz - hz), hhl),
+            (true, true, true) => ((cx + hx, cy + hy, cz + hz), hhl),
+        }
+    }
+
+    /// Gets the centre and per-axis half lengths of all eight children in index
+    /// order, whether or not they exist, such as for drawing how this node is
+    /// divided.
+    #[must_use]
+    pub fn child_bounds(&self) -> ChildBounds<S> {
+        let mut bounds = [(self.data.centre, self.data.half_lengths); 8];
+        for (idx, slot) in bounds.iter_mut().enumerate() {
+            if let Some((px, py, pz)) = Self::get_child_pos_at_idx(idx) {
+                *slot =
+                    self.get_child_centre_and_half_length_at_pos(px, py, pz);
+            }
+        }
+        bounds
+    }
+
+    /// Gets the deepest existing node that `point` belongs to, descending by
+    /// the rule used to place items in children, or `None` if it lies outside
+    /// of this node's bounds (see `contains_point`).
+    #[must_use]
+    pub fn locate(&self, point: (S, S, S)) -> Option<&Self> {
+        if !self.contains_point(point) {
+            return None;
+        }
+        let mut node = self;
+        while let Some(child) =
+            node.children[node.get_child_idx_for_point(point)].as_deref()
+        {
+            node = child;
+        }
+        Some(node)
+    }
+
+    /// Gets a mutable reference to the deepest existing node that `point`
+    /// belongs to, or `None` if it lies outside of this node's bounds. See
+    /// `locate`.
+    pub fn locate_mut(&mut self, point: (S, S, S)) -> Option<&mut Self> {
+        if self.contains_point(point) {
+            Some(self.descend_mut(point))
+        } else {
+            None
+        }
+    }
+
+    fn descend_mut(&mut self, point: (S, S, S)) -> &mut Self {
+        let idx = self.get_child_idx_for_point(point);
+        if self.children[idx].is_none() {
+            return self;
+        }
+        self.children[idx]
+            .as_deref_mut()
+            .expect("child checked above")
+            .descend_mut(point)
+    }
+
+    /// Checks whether children may be created beneath this node, which isn't
+    /// the case once `max_depth` is reached or if no axis could be split
+    /// because a child's half lengths would all be zero.
+    ///
+    /// With floating point values, deep enough children would also have their
+    /// centres rounded so far that their bounds no longer cover this node's,
+    /// and items placed in them would lie outside of them. Subdividing stops
+    /// before that happens, leaving the items in this node.
+    fn can_subdivide(&self) -> bool {
+        let two = S::one() + S::one();
+        let zero = S::default();
+        // Whether the children cover this node along an axis, and whether
+        // they split it.
+        let split_axis = |c: S, h: S| {
+            let q = h / two;
+            let hhl = h - q;
+            let (lo, hi) = (c - q, c + q);
+            let covers = lo - hhl <= c - h
+                && lo + hhl >= c
+                && hi - hhl <= c
+                && hi + hhl >= c + h;
+            (covers, q > zero && lo != hi)
+        };
+        let (cx, cy, cz) = self.data.centre;
+        let (hx, hy, hz) = self.data.half_lengths;
+        let axes = [split_axis(cx, hx), split_axis(cy, hy), split_axis(cz, hz)];
+        self.data.max_depth > 0
+            && axes.iter().all(|&(covers, _)| covers)
+            && axes.iter().any(|&(_, splits)| splits)
+    }
+}
+
+impl<D, S, M> ManagedOctree<D, S, M>
 where
-    K: Eq + Hash,
+    D: Default + Empty + Len,
     S: Default
         + Copy
         + One
+        + PartialOrd
         + Add<S, Output = S>
         + Sub<S, Output = S>
+        + Mul<S, Output = S>
         + Div<S, Output = S>,
+    M: Default,
 {
-    fn len(&self) -> usize { self.data.len }
+    /// Gets the minimum and maximum corners of the node's bounds once expanded
+    /// by `looseness`. Items with extent held by this node lie within these.
+    #[must_use]
+    pub fn loose_bounds(&self) -> ((S, S, S), (S, S, S)) {
+        let (cx, cy, cz) = self.data.centre;
+        let (hx, hy, hz) = self.loose_half_lengths(self.data.half_lengths);
+        ((cx - hx, cy - hy, cz - hz), (cx + hx, cy + hy, cz + hz))
+    }
+
+    fn loose_half_lengths(&self, (hx, hy, hz): (S, S, S)) -> (S, S, S) {
+        let k = self.data.looseness;
+        (hx + hx * k, hy + hy * k, hz + hz * k)
+    }
+
+    /// Checks whether an item may be moved into the child at `idx`, which is
+    /// the case for points, or when the child's loose bounds fully contain the
+    /// item's extent.
+    fn fits_child(
+        &self,
+        idx: usize,
+        (px, py, pz): (S, S, S),
+        (ex, ey, ez): (S, S, S),
+    ) -> bool {
+        let zero = S::default();
+        if ex == zero && ey == zero && ez == zero {
+            return true;
+        }
+        let Some((pos_x, pos_y, pos_z)) = Self::get_child_pos_at_idx(idx)
+        else {
+            return false;
+        };
+        let ((cx, cy, cz), half_lengths) =
+            self.get_child_centre_and_half_length_at_pos(pos_x, pos_y, pos_z);
+        let (hx, hy, hz) = self.loose_half_lengths(half_lengths);
+        cx - hx <= px - ex
+            && px + ex <= cx + hx
+            && cy - hy <= py - ey
+            && py + ey <= cy + hy
+            && cz - hz <= pz - ez
+            && pz + ez <= cz + hz
+    }
+}
+
+impl<D, S, T, M> ManagedOctree<D, S, M>
+where
+    D: Default
+        + Empty
+        + Len
+        + Clear
+        + IntoIterator<Item = T>
+        + OctreeCollection<T>,
+    T: CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    #[must_use]
+    pub fn new_managed(centre: (S, S, S), half_length: S) -> Self {
+        Self::new_managed_with_half_lengths(
+            centre,
+            (half_length, half_length, half_length),
+        )
+    }
+
+    /// Creates a tree spanning the axis-aligned box from `min` to `max`, which
+    /// unlike `new_managed` needn't be a cube. Each axis is halved
+    /// independently when creating children.
+    #[must_use]
+    pub fn new_managed_aabb(min: (S, S, S), max: (S, S, S)) -> Self {
+        let (centre, half_lengths) = aabb_centre_and_half_lengths(min, max);
+        Self::new_managed_with_half_lengths(centre, half_lengths)
+    }
+
+    fn new_managed_with_half_lengths(
+        centre: (S, S, S),
+        half_lengths: (S, S, S),
+    ) -> Self {
+        Self::new_with_data(ManagedOctreeData {
+            centre,
+            half_lengths,
+            ..ManagedOctreeData::default()
+        })
+    }
+
+    /// Builds a tree from `items`, partitioning them top-down in a single pass.
+    /// The default sizes are used; see `with_items` to configure them first.
+    #[must_use]
+    pub fn from_items(
+        centre: (S, S, S),
+        half_length: S,
+        items: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self::new_managed(centre, half_length).with_items(items)
+    }
+
+    /// Adds `items` and partitions them top-down in a single pass, creating
+    /// children until no node holds more than `max_size` items. This is much
+    /// cheaper than calling `add` and `rebalance` repeatedly.
+    #[must_use]
+    pub fn with_items(mut self, items: impl IntoIterator<Item = T>) -> Self {
+        for item in items {
+            self.add(item);
+        }
+        self.partition();
+        self
+    }
+
+    /// Moves every item into a new tree with the given bounds and the same
+    /// configuration, partitioned as in `with_items`.
+    ///
+    /// Items whose centre lies outside of the new bounds (see
+    /// `contains_point`) are returned alongside the new tree rather than being
+    /// added to it. Note that this excludes the lower faces, so bounds taken
+    /// directly from `content_bounds` should be padded.
+    #[must_use]
+    pub fn rebuild(self, centre: (S, S, S), half_length: S) -> (Self, Vec<T>) {
+        let mut rebuilt = Self::new_managed(centre, half_length)
+            .with_max_size(self.data.max_size)
+            .with_split_threshold(self.data.split_threshold)
+            .with_drop_below_size(self.data.drop_below_size)
+            .with_max_depth(self.data.max_depth)
+            .with_looseness(self.data.looseness)
+            .with_split_strategy(self.data.split_strategy);
+        let mut dropped = Vec::new();
+        for item in self {
+            if rebuilt.contains_point(item.centre()) {
+                rebuilt.add(item);
+            } else {
+                dropped.push(item);
+            }
+        }
+        rebuilt.partition();
+        (rebuilt, dropped)
+    }
+
+    /// Moves every item from `other`, which must have the same bounds, into
+    /// this tree and then rebalances it. This tree's configuration is kept.
+    ///
+    /// Returns the number of items the collection rejected, such as keys
+    /// already present in a `HashMap`-backed node, which are dropped.
+    ///
+    /// # Errors
+    /// Returns `other` unchanged if its centre or half lengths differ from
+    /// this tree's.
+    pub fn merge(
+        &mut self,
+        other: Self,
+    ) -> Result<usize, BoundsMismatchError<D, S, M>> {
+        if self.data.centre != other.data.centre
+            || self.data.half_lengths != other.data.half_lengths
+        {
+            return Err(BoundsMismatchError { other });
+        }
+        let mut rejected = 0;
+        for item in other {
+            if !self.add(item) {
+                rejected += 1;
+            }
+        }
+        self.rebalance();
+        Ok(rejected)
+    }
+
+    /// Set `max_size`
+    #[must_use]
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.data.max_size = max_size;
+        self
+    }
+
+    /// Changes `max_size` on this node and all of its descendants, then
+    /// rebalances any node now holding more than its split threshold, so no
+    /// follow-up call to `rebalance` is needed. Raising `max_size` leaves the
+    /// existing children in place; use `collapse` to merge them.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.data.max_size = max_size;
+        for child in self.children.iter_mut().flatten() {
+            child.set_max_size(max_size);
+        }
+        if self.over_split_threshold() {
+            self.rebalance();
+        }
+    }
+
+    /// Set `split_threshold`, the number of items a node may hold before
+    /// `rebalance` splits it, while `max_size` remains the number of items left
+    /// in the node once split. Raising it above `max_size` adds hysteresis, so
+    /// a node whose item count hovers around `max_size` isn't repeatedly split
+    /// and collapsed. Values below `max_size` have no effect.
+    #[must_use]
+    pub const fn with_split_threshold(
+        mut self,
+        split_threshold: usize,
+    ) -> Self {
+        self.data.split_threshold = split_threshold;
+        self
+    }
+
+    /// Set `max_depth`, the number of levels of children that `rebalance` may
+    /// create beneath this node.
+    #[must_use]
+    pub const fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.data.max_depth = max_depth;
+        self
+    }
+
+    /// Set `looseness`, turning this into a loose octree for items with
+    /// extent. Each child's bounds are expanded by `factor` times its half
+    /// lengths on every side when deciding whether an item fits, and an item is
+    /// only moved into the child containing its centre if the expanded bounds
+    /// hold all of it, otherwise it stays in this node. This keeps objects that
+    /// straddle a split plane from being confined to a child that is too small
+    /// for them. Points are placed by their centre alone, regardless of this.
+    #[must_use]
+    pub const fn with_looseness(mut self, factor: S) -> Self {
+        self.data.looseness = factor;
+        self
+    }
+
+    /// Set the `SplitStrategy` used by `rebalance` when dividing a node into
+    /// children, which is `SplitStrategy::Midpoint` by default. A node's split
+    /// is chosen when it's first divided and kept while it has children.
+    #[must_use]
+    pub const fn with_split_strategy(
+        mut self,
+        strategy: SplitStrategy,
+    ) -> Self {
+        self.data.split_strategy = strategy;
+        self
+    }
+
+    /// Set `drop_below_size`
+    ///
+    /// # Panics
+    /// Panics when set to 0
+    #[must_use]
+    pub fn with_drop_below_size(mut self, drop_below_size: usize) -> Self {
+        assert!(
+            drop_below_size > 0,
+            "drop_below_size must be greater than 0"
+        );
+
+        self.data.drop_below_size = drop_below_size;
+        self
+    }
+
+    /// Adds data to the node without flushing/rebalancing the tree, returning
+    /// whether it was inserted. Items are rejected if the underlying
+    /// collection refuses them, such as a key already in this node's
+    /// `HashMap`.
+    pub fn add(&mut self, item: T) -> bool {
+        let inserted = self.data.data.add(item).is_some();
+        if inserted {
+            grow_len(&mut self.data.len, 1);
+        }
+        inserted
+    }
+
+    /// Adds data to the node like `add`, then rebalances if this node now holds
+    /// more than its split threshold. Items therefore only pile up until the
+    /// next split, avoiding the need to call `rebalance` manually.
+    pub fn insert(&mut self, item: T) -> bool {
+        let inserted = self.add(item);
+        if self.over_split_threshold() {
+            self.rebalance();
+        }
+        inserted
+    }
+
+    /// Adds data like `add`, but to the deepest existing node along the path
+    /// to the octant the item belongs to, as `rebalance` would place it,
+    /// without creating or splitting any nodes. This keeps items out of the
+    /// root's buffer between rebalances.
+    pub fn add_routed(&mut self, item: T) -> bool {
+        let centre = item.centre();
+        let idx = self.get_child_idx_for_point(centre);
+        if !self.fits_child(idx, centre, item.half_extents()) {
+            return self.add(item);
+        }
+        match self.children[idx].as_deref_mut() {
+            Some(child) => {
+                let inserted = child.add_routed(item);
+                if inserted {
+                    grow_len(&mut self.data.len, 1);
+                }
+                inserted
+            }
+            None => self.add(item),
+        }
+    }
+
+    /// Checks whether `item`'s centre lies within this node's bounds, using
+    /// the same convention as `contains_point` and `rebalance`: a centre on a
+    /// node's upper face belongs to it, while one on its lower face belongs to
+    /// the neighbouring node.
+    #[must_use]
+    pub fn item_belongs_here(&self, item: &T) -> bool {
+        self.contains_point(item.centre())
+    }
+
+    /// Adds data to the node without flushing/rebalancing the tree, provided
+    /// its centre lies within the node's bounds. As with `add`, returns whether
+    /// the underlying collection accepted the item.
+    ///
+    /// # Errors
+    /// Returns an error if the item's centre lies outside of the node's bounds.
+    pub fn try_add(&mut self, item: T) -> Result<bool, OutOfBoundsError<S>> {
+        let point = item.centre();
+        if self.contains_point(point) {
+            Ok(self.add(item))
+        } else {
+            Err(OutOfBoundsError {
+                point,
+                centre: self.data.centre,
+                half_lengths: self.data.half_lengths,
+            })
+        }
+    }
+
+    /// Clears data from the node (not the whole tree)
+    ///
+    /// # Panics
+    /// Panics if this node's count of the items in its subtree is smaller than
+    /// the number it holds itself, which means that count had gone wrong.
+    pub fn clear_data(&mut self) {
+        shrink_len(&mut self.data.len, self.data.data.len());
+        self.data.data.clear();
+    }
+
+    /// Clears data from the whole tree and removes all children, keeping this
+    /// node's bounds and configuration so that it can be reused. The split
+    /// point chosen for the old items and any metadata are reset too, leaving
+    /// the node as it was when created.
+    pub fn clear(&mut self) {
+        self.children = Default::default();
+        self.data.data.clear();
+        self.data.len = 0;
+        self.data.split = None;
+        self.data.meta = M::default();
+    }
+
+    /// Moves items into child nodes once this node holds more than its split
+    /// threshold (see `with_split_threshold`), creating children for the most
+    /// populated octants until no more than `max_size` items remain in this
+    /// node. Any child left holding more than its split threshold is then
+    /// rebalanced too.
+    /// No children are created once `max_depth` is reached, leaving any excess
+    /// items in that node.
+    ///
+    /// Octants holding equally many items are split in ascending index order,
+    /// so rebalancing the same items always produces the same tree.
+    ///
+    /// Any node can be rebalanced on its own, such as a child reached through
+    /// `get_child_mut`, as each node carries its own bounds and configuration.
+    /// Rebalancing never changes how many items a subtree holds, so the counts
+    /// kept by its ancestors stay correct.
+    ///
+    /// # Panics
+    /// Panics if a child is created in an occupied octant, which cannot happen
+    /// as items belonging to existing children are moved there first.
+    pub fn rebalance(&mut self) {
+        self.rebalance_node();
+        for child in self.children.iter_mut().flatten() {
+            if child.over_split_threshold() {
+                child.rebalance();
+            }
+        }
+    }
+
+    /// Performs one step of `rebalance`, splitting every node at the shallowest
+    /// depth holding more than its split threshold without recursing further.
+    /// Returns whether any work was done, so the rebalancing of a freshly
+    /// filled tree can be spread over several calls until it returns `false`,
+    /// giving the same result as a single `rebalance`.
+    ///
+    /// Depths whose nodes are only over their split threshold because of items
+    /// that can't be moved into a child (such as items too large for any of
+    /// them) are skipped, so this doesn't keep returning `true` for them.
+    ///
+    /// # Panics
+    /// Panics if a child is created in an occupied octant, which cannot happen
+    /// as items belonging to existing children are moved there first.
+    pub fn rebalance_one_level(&mut self) -> bool {
+        let mut depths: Vec<u32> = self
+            .iter_nodes()
+            .filter(|(node, _)| node.needs_rebalance())
+            .map(|(_, depth)| depth)
+            .collect();
+        depths.dedup();
+        depths
+            .into_iter()
+            .any(|depth| self.rebalance_at_depth(depth))
+    }
+
+    /// Rebalances every node at `depth` below this one, returning whether any
+    /// items were moved.
+    fn rebalance_at_depth(&mut self, depth: u32) -> bool {
+        if depth == 0 {
+            self.needs_rebalance() && self.rebalance_node()
+        } else {
+            let mut moved = false;
+            for child in self.children.iter_mut().flatten() {
+                moved |= child.rebalance_at_depth(depth - 1);
+            }
+            moved
+        }
+    }
+
+    /// Checks whether this node holds more than its split threshold and may be
+    /// split.
+    fn needs_rebalance(&self) -> bool {
+        self.over_split_threshold() && self.can_subdivide()
+    }
+
+    /// Performs a single level of `rebalance` on this node without recursing
+    /// into its children, returning whether any items were moved out of it.
+    fn rebalance_node(&mut self) -> bool {
+        let before = self.data.data.len();
+        self.choose_split();
+        let bucket_counts = self.move_to_existing_children();
+        if self.needs_rebalance() {
+            let bucket_sizes = Self::sort_bucket_sizes(bucket_counts);
+            let mut new_size = self.data.data.len();
+            for (max_idx, max_val) in bucket_sizes {
+                // Items too large for any child leave buckets empty.
+                if max_val == 0 {
+                    break;
+                }
+                let Some((px, py, pz)) = Self::get_child_pos_at_idx(max_idx)
+                else {
+                    continue;
+                };
+                let (centre, half_lengths) =
+                    self.get_child_centre_and_half_length_at_pos(px, py, pz);
+                self.add_child(max_idx, self.new_child(centre, half_lengths))
+                    .unwrap();
+                new_size -= max_val;
+                if new_size <= self.data.max_size {
+                    break;
+                }
+            }
+            self.move_to_existing_children();
+        }
+        self.data.data.len() < before
+    }
+
+    /// Distributes every item in this node between all eight octants,
+    /// recursing into any child that then holds more than its split threshold.
+    fn partition(&mut self) {
+        if !self.needs_rebalance() {
+            return;
+        }
+        self.choose_split();
+        let mut buckets: [D; 8] = Default::default();
+        for item in mem::take(&mut self.data.data) {
+            let centre = item.centre();
+            let idx = self.get_child_idx_for_point(centre);
+            if self.fits_child(idx, centre, item.half_extents()) {
+                buckets[idx].add(item);
+            } else {
+                self.data.data.add(item);
+            }
+        }
+        for (idx, bucket) in IntoIterator::into_iter(buckets).enumerate() {
+            if bucket.is_empty() {
+                continue;
+            }
+            if self.children[idx].is_none() {
+                if let Some((px, py, pz)) = Self::get_child_pos_at_idx(idx) {
+                    let (centre, half_lengths) = self
+                        .get_child_centre_and_half_length_at_pos(px, py, pz);
+                    self.children[idx] =
+                        Some(Box::new(self.new_child(centre, half_lengths)));
+                }
+            }
+            if let Some(child) = &mut self.children[idx] {
+                for item in bucket {
+                    child.add(item);
+                }
+                child.partition();
+            }
+        }
+    }
+
+    /// Places the split planes of a node about to be split for the first time
+    /// at the mean of its items under `SplitStrategy::Centroid`. The midpoint
+    /// is kept if every item shares the same centre, as splitting at it would
+    /// leave them all in one child, or if rounding would leave the children
+    /// not covering the node.
+    fn choose_split(&mut self) {
+        if self.data.split_strategy != SplitStrategy::Centroid
+            || !self.is_leaf()
+            || !self.needs_rebalance()
+        {
+            return;
+        }
+        let zero = S::default();
+        let mut count = zero;
+        let mut sum = (zero, zero, zero);
+        let mut first = None;
+        let mut spread = false;
+        for item in mem::take(&mut self.data.data) {
+            let (x, y, z) = item.centre();
+            count = count + S::one();
+            sum = (sum.0 + x, sum.1 + y, sum.2 + z);
+            match first {
+                None => first = Some((x, y, z)),
+                Some(p) => spread = spread || p != (x, y, z),
+            }
+            self.data.data.add(item);
+        }
+        self.data.split = None;
+        if !spread {
+            return;
+        }
+        let (cx, cy, cz) = self.data.centre;
+        let (hx, hy, hz) = self.data.half_lengths;
+        let (x, x_covers) = centroid_axis(cx, hx, sum.0 / count);
+        let (y, y_covers) = centroid_axis(cy, hy, sum.1 / count);
+        let (z, z_covers) = centroid_axis(cz, hz, sum.2 / count);
+        if x_covers && y_covers && z_covers {
+            self.data.split = Some((x, y, z));
+        }
+    }
+
+    /// Creates an empty child with the same configuration as this node.
+    fn new_child(&self, centre: (S, S, S), half_lengths: (S, S, S)) -> Self {
+        Self::new_managed_with_half_lengths(centre, half_lengths)
+            .with_max_size(self.data.max_size)
+            .with_split_threshold(self.data.split_threshold)
+            .with_drop_below_size(self.data.drop_below_size)
+            .with_max_depth(self.data.max_depth - 1)
+            .with_looseness(self.data.looseness)
+            .with_split_strategy(self.data.split_strategy)
+    }
+
+    /// Removes children, bottom-up, whose subtree holds fewer than
+    /// `drop_below_size` items, moving their items into this node. This is the
+    /// inverse of `rebalance`.
+    ///
+    /// Items this node's collection would reject, such as keys it already
+    /// holds, are left in the child, which is then kept, so no items are lost
+    /// and `len` stays equal to the number of items stored.
+    pub fn collapse(&mut self) {
+        for idx in 0..self.children.len() {
+            if let Some(child) = self.children[idx].as_deref_mut() {
+                child.collapse();
+                if child.is_leaf()
+                    && child.data.len < child.data.drop_below_size
+                {
+                    self.merge_leaf_child(idx);
+                }
+            }
+        }
+    }
+
+    /// Moves the items of the leaf child at `idx` into this node, removing the
+    /// child once it's empty. Items this node's collection would reject are
+    /// left in the child. Returns whether the child was removed.
+    fn merge_leaf_child(&mut self, idx: usize) -> bool {
+        let Some(child) = self.children[idx].as_deref_mut() else {
+            return false;
+        };
+        for item in mem::take(&mut child.data.data) {
+            if self.data.data.accepts(&item) {
+                self.data.data.add(item);
+                shrink_len(&mut child.data.len, 1);
+            } else {
+                child.data.data.add(item);
+            }
+        }
+        if child.data.data.is_empty() {
+            self.children[idx] = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves the items of the child at `idx` into this node and removes it, as
+    /// `collapse` would, if that child is a leaf holding fewer than
+    /// `drop_below_size` items. Only that child is visited, so this can tidy up
+    /// the branch an item was just removed from without sweeping the whole
+    /// tree. As with `collapse`, items this node's collection would reject are
+    /// left in the child, which is then kept.
+    ///
+    /// Returns whether the child was removed, which is never the case if it
+    /// doesn't exist.
+    pub fn try_collapse_node(&mut self, idx: usize) -> bool {
+        let Some(child) =
+            self.children.get_mut(idx).and_then(Option::as_deref_mut)
+        else {
+            return false;
+        };
+        if !child.is_leaf() || child.data.len >= child.data.drop_below_size {
+            return false;
+        }
+        self.merge_leaf_child(idx)
+    }
+
+    fn sort_bucket_sizes(sizes: [usize; 8]) -> Vec<(usize, usize)> {
+        let mut bucket_sizes: Vec<(usize, usize)> =
+            sizes.iter().enumerate().map(|(i, &v)| (i, v)).collect();
+        bucket_sizes
+            .sort_unstable_by(|(ai, am), (bi, bm)| bm.cmp(am).then(ai.cmp(bi)));
+        bucket_sizes
+    }
+
+    /// Moves any objects that should belong to a child to that child if it
+    /// exists, unless the child's collection would reject them (such as a key
+    /// it already holds), in which case they stay in this node. Returns the
+    /// bucket sizes of any remaining items.
+    fn move_to_existing_children(&mut self) -> [usize; 8] {
+        let mut result = [0; 8];
+        let mut old_d = D::default();
+        mem::swap(&mut old_d, &mut self.data.data);
+        for item in old_d {
+            let centre = item.centre();
+            let idx = self.get_child_idx_for_point(centre);
+            if !self.fits_child(idx, centre, item.half_extents()) {
+                self.data.data.add(item);
+            } else if let Some(child) = self.children[idx]
+                .as_deref_mut()
+                .filter(|child| child.data.data.accepts(&item))
+            {
+                child.add(item);
+            } else {
+                self.data.data.add(item);
+                if self.children[idx].is_none() {
+                    result[idx] += 1;
+                }
+            }
+        }
+
+        result
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{ManagedHashMapOctree, ManagedVecOctree};
-    use len_trait::Len;
+impl<D, S, T, M> Extend<T> for ManagedOctree<D, S, M>
+where
+    D: Default
+        + Empty
+        + Len
+        + Clear
+        + IntoIterator<Item = T>
+        + OctreeCollection<T>,
+    T: CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Adds every item with `add`, without rebalancing.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(item);
+        }
+    }
+}
+
+impl<T, S, M> ManagedVecOctree<T, S, M>
+where
+    T: CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Creates an empty tree whose root buffer has room for `capacity` items
+    /// before reallocating, e.g. ahead of a large `extend`.
+    #[must_use]
+    pub fn new_managed_with_capacity(
+        centre: (S, S, S),
+        half_length: S,
+        capacity: usize,
+    ) -> Self {
+        let mut o = Self::new_managed(centre, half_length);
+        o.data.data = Vec::with_capacity(capacity);
+        o
+    }
+
+    /// Removes and returns an item whose centre is exactly `point`, if one
+    /// exists.
+    ///
+    /// Only the nodes along the path to the octant containing `point` are
+    /// searched, following the same rule used when placing items.
+    pub fn remove_item(&mut self, point: (S, S, S)) -> Option<T> {
+        let removed = if let Some(pos) = self
+            .data
+            .data
+            .iter()
+            .position(|item| item.centre() == point)
+        {
+            Some(self.data.data.remove(pos))
+        } else {
+            let idx = self.get_child_idx_for_point(point);
+            self.children[idx].as_mut()?.remove_item(point)
+        };
+        if removed.is_some() {
+            shrink_len(&mut self.data.len, 1);
+        }
+        removed
+    }
+
+    /// Adds an item like `insert`, rebalancing if needed, and returns the path
+    /// of child indices (see `get_by_path`) to the node that ends up holding
+    /// it. This is the node `remove_item` would take it from, so if another
+    /// item has the same centre, the path may lead to that one's node instead.
+    /// The path is only valid until the structure of the tree next changes.
+    pub fn add_tracked(&mut self, item: T) -> Vec<usize> {
+        let point = item.centre();
+        self.insert(item);
+        let mut path = Vec::new();
+        let mut node: &Self = self;
+        while !node.data.data.iter().any(|item| item.centre() == point) {
+            let idx = node.get_child_idx_for_point(point);
+            let Some(child) = node.children[idx].as_deref() else {
+                break;
+            };
+            path.push(idx);
+            node = child;
+        }
+        path
+    }
+
+    /// Moves the item whose centre is exactly `old` to `new`, returning
+    /// whether it was moved. The item is re-added to this node without
+    /// rebalancing, so call `rebalance` once all items have been moved.
+    ///
+    /// If `new` lies outside of this node's bounds (see `contains_point`) the
+    /// item is left where it is and `false` is returned, as it is if no item
+    /// lies at `old`.
+    pub fn update_position(&mut self, old: (S, S, S), new: (S, S, S)) -> bool
+    where
+        T: MovableItem<S>,
+    {
+        if !self.contains_point(new) {
+            return false;
+        }
+        let Some(mut item) = self.remove_item(old) else {
+            return false;
+        };
+        item.set_centre(new);
+        self.add(item);
+        true
+    }
+
+    /// Removes every item for which `f` returns `false`, visiting nodes
+    /// depth-first. Children left sparse are kept; call `collapse` afterwards
+    /// to merge them.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.retain_with(&mut f);
+    }
+
+    fn retain_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.data.data.retain(|item| f(item));
+        let mut len = self.data.data.len();
+        for child in self.children.iter_mut().flatten() {
+            child.retain_with(f);
+            grow_len(&mut len, child.data.len);
+        }
+        self.data.len = len;
+    }
+
+    /// Calls `f` on every item in the tree, visiting nodes depth-first, such as
+    /// to advance a simulation by a step. Items stay in the nodes holding them,
+    /// so `len` is unaffected.
+    ///
+    /// Changing an item's centre can leave it in a node that no longer contains
+    /// it. `rebalance` only moves items down into children, so call `rebuild`
+    /// afterwards if items may have left their nodes, or `rebalance` if they
+    /// only moved within them.
+    pub fn for_each_item_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut T),
+    {
+        self.for_each_item_mut_with(&mut f);
+    }
+
+    fn for_each_item_mut_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut T),
+    {
+        self.data.data.iter_mut().for_each(&mut *f);
+        for child in self.children.iter_mut().flatten() {
+            child.for_each_item_mut_with(f);
+        }
+    }
+
+    /// Counts how many of the items in this node's own buffer belong to each
+    /// octant, by the rule used to place items in children, without moving
+    /// them. This shows how skewed the items are before calling `rebalance`.
+    #[must_use]
+    pub fn root_octant_histogram(&self) -> [usize; 8] {
+        let mut counts = [0; 8];
+        for item in &self.data.data {
+            counts[self.get_child_idx_for_point(item.centre())] += 1;
+        }
+        counts
+    }
+
+    /// Sets the metadata of every node bottom-up, passing `f` the node's own
+    /// items along with the freshly computed metadata of its children in index
+    /// order. Metadata isn't updated as items are added or moved, so call this
+    /// again after changing the tree.
+    pub fn recompute_meta<F>(&mut self, f: F)
+    where
+        F: Fn(&[T], &[&M]) -> M,
+    {
+        self.recompute_meta_with(&f);
+    }
+
+    fn recompute_meta_with<F>(&mut self, f: &F)
+    where
+        F: Fn(&[T], &[&M]) -> M,
+    {
+        for child in self.children.iter_mut().flatten() {
+            child.recompute_meta_with(f);
+        }
+        let child_metas: Vec<&M> = self
+            .children
+            .iter()
+            .flatten()
+            .map(|c| &c.data.meta)
+            .collect();
+        let meta = f(&self.data.data, &child_metas);
+        self.data.meta = meta;
+    }
+
+    /// Shrinks the capacity of every node's buffer as much as possible,
+    /// leaving the structure of the tree unchanged.
+    pub fn shrink_to_fit(&mut self) {
+        self.data.data.shrink_to_fit();
+        for child in self.children.iter_mut().flatten() {
+            child.shrink_to_fit();
+        }
+    }
+}
+
+impl<T, S, M> FromIterator<T> for ManagedVecOctree<T, S, M>
+where
+    T: CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Builds a tree whose root is a cube centred on the items, partitioning
+    /// them top-down as in `with_items`. Its half length is a quarter more than
+    /// the smallest cube containing them would need (or one more, for
+    /// integers), as the lower faces of a node aren't part of it (see
+    /// `contains_point`) and items on the minimum corner would otherwise
+    /// lie outside. A half length of zero, as given by a single item, is
+    /// widened to one, and an empty iterator gives an empty tree centred on
+    /// the origin.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        let Some((min, max)) =
+            point_bounds(items.iter().map(CentredItem::centre))
+        else {
+            return Self::new_managed(Default::default(), S::one());
+        };
+        let (centre, (hx, hy, hz)) = aabb_centre_and_half_lengths(min, max);
+        let mut half_length = hx;
+        if hy > half_length {
+            half_length = hy;
+        }
+        if hz > half_length {
+            half_length = hz;
+        }
+        if half_length <= S::default() {
+            half_length = S::one();
+        } else {
+            let two = S::one() + S::one();
+            let pad = half_length / two / two;
+            half_length =
+                half_length + if pad > S::default() { pad } else { S::one() };
+        }
+        Self::from_items(centre, half_length, items)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, M> ManagedHashMapOctree<K, V, S, M>
+where
+    K: Eq + Hash,
+    (K, V): CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Removes and returns the entry for `key`, if one exists in the nodes
+    /// along the path to the octant containing `point`.
+    pub fn remove_key(&mut self, key: &K, point: (S, S, S)) -> Option<(K, V)> {
+        let removed = if let Some(entry) = self.data.data.remove_entry(key) {
+            Some(entry)
+        } else {
+            let idx = self.get_child_idx_for_point(point);
+            self.children[idx].as_mut()?.remove_key(key, point)
+        };
+        if removed.is_some() {
+            shrink_len(&mut self.data.len, 1);
+        }
+        removed
+    }
+}
+
+impl<T, S, M> Empty for ManagedVecOctree<T, S, M>
+where
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    fn is_empty(&self) -> bool { self.data.len == 0 }
+}
+
+impl<T, S, M> Len for ManagedVecOctree<T, S, M>
+where
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    fn len(&self) -> usize { self.data.len }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, M> Empty for ManagedHashMapOctree<K, V, S, M>
+where
+    K: Eq + Hash,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    fn is_empty(&self) -> bool { self.data.len == 0 }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, M> Len for ManagedHashMapOctree<K, V, S, M>
+where
+    K: Eq + Hash,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    fn len(&self) -> usize { self.data.len }
+}
+
+impl<K, V, S, M> Empty for ManagedBTreeMapOctree<K, V, S, M>
+where
+    K: Ord,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    fn is_empty(&self) -> bool { self.data.len == 0 }
+}
+
+impl<K, V, S, M> Len for ManagedBTreeMapOctree<K, V, S, M>
+where
+    K: Ord,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    fn len(&self) -> usize { self.data.len }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use super::ManagedHashMapOctree;
+    use super::{
+        AddChildError,
+        CentredItem,
+        ManagedBTreeMapOctree,
+        ManagedVecOctree,
+        MovableItem,
+        Octree,
+        SplitStrategy,
+        octant_index,
+    };
+    use len_trait::{Empty, Len};
+
+    #[test]
+    fn test_with_drop_below_size() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_drop_below_size(3);
+        assert_eq!(o.data.drop_below_size, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "drop_below_size must be greater than 0")]
+    fn test_with_drop_below_size_0_panics() {
+        let _ = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_drop_below_size(0);
+    }
+
+    #[test]
+    fn test_with_max_size() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(3);
+        assert_eq!(o.data.max_size, 3);
+    }
+
+    #[test]
+    fn test_clone_before_rebalance() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((-1.0, -1.0, -1.0));
+        let snapshot = o.clone();
+        o.rebalance();
+        assert!(o.get_child(7).is_some());
+        assert!(snapshot.get_child(7).is_none());
+        assert_eq!(snapshot.data.data.len(), 3);
+        assert_eq!(snapshot.len(), o.len());
+    }
+
+    #[test]
+    fn test_debug_shows_bounds() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 2.0, 3.0),
+            10.0,
+        );
+        let formatted = format!("{o:?}");
+        assert!(formatted.contains("centre: (1.0, 2.0, 3.0)"));
+        assert!(formatted.contains("half_lengths: (10.0, 10.0, 10.0)"));
+    }
+
+    #[test]
+    fn test_with_max_depth() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_depth(3);
+        assert_eq!(o.data.max_depth, 3);
+    }
+
+    #[test]
+    fn test_rebalance_max_depth_identical_points() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2)
+        .with_max_depth(2);
+        for _ in 0..10 {
+            o.add((1.0, 1.0, 1.0));
+        }
+        let mut node = &mut o;
+        loop {
+            node.rebalance();
+            let idx = node.get_child_idx_for_point((1.0, 1.0, 1.0));
+            match node.get_child_mut(idx) {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        assert_eq!(o.depth(), 2);
+        assert_eq!(o.len(), 10);
+        let leaf = o.get_child(7).unwrap().get_child(0).unwrap();
+        assert_eq!(leaf.data.max_depth, 0);
+        assert_eq!(leaf.data.data.len(), 10);
+    }
+
+    #[test]
+    fn test_bounds_accessors() {
+        let o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed(
+            (1, 2, 3),
+            10,
+        );
+        assert_eq!(o.centre(), (1, 2, 3));
+        assert_eq!(o.half_lengths(), (10, 10, 10));
+        assert_eq!(o.bounds(), ((-9, -8, -7), (11, 12, 13)));
+    }
+
+    #[test]
+    fn test_size_accessors() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            10.0,
+        )
+        .with_max_size(5)
+        .with_drop_below_size(2);
+        assert_eq!(o.max_size(), 5);
+        assert_eq!(o.drop_below_size(), 2);
+    }
+
+    #[test]
+    fn test_set_max_size() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, 1.0, -1.0));
+        o.rebalance();
+        assert_eq!(o.node_count(), 1);
+
+        o.set_max_size(1);
+        assert_eq!(o.max_size(), 1);
+        assert!(o.node_count() > 1);
+        assert_eq!(o.len(), 4);
+        for (node, _) in o.iter_nodes() {
+            assert_eq!(node.max_size(), 1);
+            assert!(node.data.get_data().len() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.extend(vec![(1.0, 1.0, 1.0), (2.0, 2.0, 1.0), (-1.0, -1.0, -1.0)]);
+        assert_eq!(o.len(), 3);
+        assert_eq!(o.node_count(), 1);
+    }
+
+    #[test]
+    fn test_from_iter_bounds() {
+        let points = vec![
+            (1.0, 2.0, 3.0),
+            (-4.0, 0.0, 1.0),
+            (2.0, 6.0, -1.0),
+            (0.5, 0.5, 0.5),
+        ];
+        let o: ManagedVecOctree<(f32, f32, f32), f32> =
+            points.iter().copied().collect();
+        assert_eq!(o.len(), points.len());
+        assert_eq!(o.centre(), (-1.0, 3.0, 1.0));
+        assert_eq!(o.half_lengths(), (3.75, 3.75, 3.75));
+        for p in points {
+            assert!(o.contains_point(p));
+        }
+
+        let mut o: ManagedVecOctree<(f32, f32, f32), f32> =
+            vec![(0.0, 0.0, 0.0), (10.0, 10.0, 10.0)]
+                .into_iter()
+                .collect();
+        assert!(o.contains_point((0.0, 0.0, 0.0)));
+        assert!(o.try_add((0.0, 0.0, 0.0)).is_ok());
+
+        let o: ManagedVecOctree<(i32, i32, i32), i32> =
+            vec![(0, 0, 0), (10, 10, 10), (3, 0, 7)]
+                .into_iter()
+                .collect();
+        assert!(o.iter().all(|&p| o.contains_point(p)));
+    }
+
+    #[test]
+    fn test_from_iter_single_and_empty() {
+        let o: ManagedVecOctree<(f32, f32, f32), f32> =
+            std::iter::once((5.0, 5.0, 5.0)).collect();
+        assert_eq!(o.centre(), (5.0, 5.0, 5.0));
+        assert_eq!(o.half_lengths(), (1.0, 1.0, 1.0));
+        assert_eq!(o.len(), 1);
+
+        let o: ManagedVecOctree<(f32, f32, f32), f32> =
+            std::iter::empty().collect();
+        assert_eq!(o.centre(), (0.0, 0.0, 0.0));
+        assert!(o.is_empty());
+    }
+
+    #[test]
+    fn test_new_managed_with_capacity() {
+        let mut o: ManagedVecOctree<(f32, f32, f32), f32> =
+            ManagedVecOctree::new_managed_with_capacity(
+                (0.0, 0.0, 0.0),
+                1000.0,
+                64,
+            )
+            .with_max_size(4);
+        assert!(o.data.get_data().capacity() >= 64);
+        o.extend(random_points(64));
+        assert_eq!(o.len(), 64);
+        o.rebalance();
+        assert_eq!(o.len(), 64);
+        assert_eq!(o.iter().count(), 64);
+    }
+
+    #[test]
+    fn test_update_position_across_plane() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((-1.0, 1.0, 1.0));
+        o.add((-3.0, 3.0, 3.0));
+        o.rebalance();
+        assert!(o.update_position((2.0, 2.0, 2.0), (-2.0, 2.0, 2.0)));
+        o.rebalance();
+        assert_eq!(o.len(), 4);
+        assert!(
+            o.items_in_child_at_pos(false, true, true)
+                .contains(&&(-2.0, 2.0, 2.0))
+        );
+        assert_eq!(
+            o.items_in_child_at_pos(true, true, true),
+            vec![&(1.0, 1.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_update_position_not_moved() {
+        let mut o = ManagedVecOctree::<[f32; 3], f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            10.0,
+        );
+        o.add([1.0, 1.0, 1.0]);
+        assert!(!o.update_position((5.0, 5.0, 5.0), (1.0, 1.0, 1.0)));
+        assert!(!o.update_position((1.0, 1.0, 1.0), (20.0, 1.0, 1.0)));
+        assert_eq!(o.len(), 1);
+        assert_eq!(o.iter().collect::<Vec<_>>(), vec![&[1.0, 1.0, 1.0]]);
+    }
+
+    #[test]
+    fn test_set_centre_keyed() {
+        let mut item = ("a", (1.0, 2.0, 3.0));
+        item.set_centre((4.0, 5.0, 6.0));
+        assert_eq!(item.centre(), (4.0, 5.0, 6.0));
+    }
+
+    #[test]
+    fn test_rebuild_expanded() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            10.0,
+        )
+        .with_max_size(2)
+        .with_drop_below_size(2);
+        let points = vec![
+            (1.0, 1.0, 1.0),
+            (2.0, 2.0, 1.0),
+            (-1.0, -1.0, -1.0),
+            (50.0, 50.0, 50.0),
+            (-30.0, 20.0, 5.0),
+        ];
+        o.extend(points.iter().copied());
+        o.rebalance();
+        let (r, dropped) = o.rebuild((0.0, 0.0, 0.0), 100.0);
+        assert!(dropped.is_empty());
+        assert_eq!(r.len(), points.len());
+        assert_eq!(r.half_lengths(), (100.0, 100.0, 100.0));
+        assert_eq!(r.max_size(), 2);
+        assert_eq!(r.drop_below_size(), 2);
+        assert!(r.node_count() > 1);
+        let mut items: Vec<_> = r.iter().copied().collect();
+        items.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut expected = points;
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn test_rebuild_returns_dropped() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            100.0,
+        )
+        .with_items(vec![(1.0, 1.0, 1.0), (50.0, 0.0, 0.0)]);
+        let (r, dropped) = o.rebuild((0.0, 0.0, 0.0), 10.0);
+        assert_eq!(r.len(), 1);
+        assert_eq!(dropped, vec![(50.0, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_rebalance_one_level_converges() {
+        let mut seq = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4);
+        seq.extend(random_points(500));
+        let mut stepped = seq.clone();
+        seq.rebalance();
+
+        let mut steps = 0;
+        while stepped.rebalance_one_level() {
+            steps += 1;
+        }
+        assert_eq!(steps, seq.depth());
+        assert_eq!(stepped, seq);
+        assert!(!stepped.rebalance_one_level());
+    }
+
+    #[test]
+    fn test_retain_positive_x() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2);
+        let points = random_points(100);
+        o.extend(points.iter().copied());
+        o.rebalance();
+        o.retain(|p| p.0 > 0.0);
+        assert!(o.validate().is_ok());
+
+        let mut expected: Vec<_> =
+            points.into_iter().filter(|p| p.0 > 0.0).collect();
+        let mut survivors: Vec<_> = o.iter().copied().collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        survivors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(survivors, expected);
+        assert_eq!(o.len(), expected.len());
+        for (node, _) in o.iter_nodes() {
+            assert_eq!(node.len(), node.iter().count());
+        }
+    }
+
+    #[test]
+    fn test_locate() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+
+        let point = (1.5, 1.5, 1.5);
+        let node = o.locate(point).unwrap();
+        assert!(node.is_leaf());
+        assert!(node.contains_point(point));
+        assert!(o.depth() > 0);
+        assert!(o.locate((2000.0, 0.0, 0.0)).is_none());
+
+        let node = o.locate_mut((-3.0, -3.0, -3.0)).unwrap();
+        assert!(node.contains_point((-3.0, -3.0, -3.0)));
+        node.add((-3.0, -3.0, -3.0));
+        assert!(o.locate_mut((0.0, 2000.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_approx_size_bytes() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4);
+        let empty = o.approx_size_bytes();
+        assert!(empty >= std::mem::size_of_val(&o));
+        o.extend(random_points(200));
+        o.rebalance();
+        let full = o.approx_size_bytes();
+        assert!(full >= empty + 200 * std::mem::size_of::<(f32, f32, f32)>());
+        o.clear();
+        assert!(o.approx_size_bytes() < full);
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2);
+        let points = random_points(64);
+        o.extend(points.iter().copied());
+        o.rebalance();
+        let kept = points[0];
+        for &p in &points[1..] {
+            assert_eq!(o.remove_item(p), Some(p));
+        }
+        let node_count = o.node_count();
+        o.shrink_to_fit();
+        assert_eq!(o.len(), 1);
+        assert_eq!(o.node_count(), node_count);
+        assert_eq!(o.remove_item(kept), Some(kept));
+    }
+
+    #[test]
+    fn test_is_node_empty() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        assert!(o.is_node_empty());
+        assert!(o.data_if_nonempty().is_none());
+        o.add((1.0, 1.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        assert_eq!(o.data_if_nonempty().map(Len::len), Some(2));
+        o.rebalance();
+        o.clear_data();
+        assert!(o.is_node_empty());
+        assert!(o.data_if_nonempty().is_none());
+        assert!(!o.is_empty());
+        assert!(
+            o.iter_nodes()
+                .skip(1)
+                .any(|(node, _)| !node.is_node_empty())
+        );
+    }
+
+    #[test]
+    fn test_partial_eq_is_structural() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        let unbalanced = o.clone();
+        o.rebalance();
+        assert_ne!(o, unbalanced);
+
+        let mut c = o.clone();
+        assert_eq!(c, o);
+        c.add((2.0, 2.0, 2.0));
+        assert_ne!(c, o);
+
+        let moved = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 0.0, 0.0),
+            1000.0,
+        );
+        let empty = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        assert_ne!(moved, empty);
+    }
+
+    #[test]
+    fn test_get_child_centre_and_half_length_integer() {
+        let o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed_aabb(
+            (-5, 0, 0),
+            (0, 5, 4),
+        );
+        assert_eq!(o.centre(), (-2, 2, 2));
+        assert_eq!(o.half_lengths(), (3, 3, 2));
+        let (centre, half_lengths) =
+            o.get_child_centre_and_half_length_at_pos(false, true, true);
+        assert_eq!(centre, (-3, 3, 3));
+        assert_eq!(half_lengths, (2, 2, 1));
+    }
+
+    #[test]
+    fn test_get_child_centre_and_half_length_neg() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        let ((cx, cy, cz), (hx, hy, hz)) =
+            o.get_child_centre_and_half_length_at_pos(false, false, false);
+        assert_relative_eq!(cx, -500.0);
+        assert_relative_eq!(cy, -500.0);
+        assert_relative_eq!(cz, -500.0);
+        assert_relative_eq!(hx, 500.0);
+        assert_relative_eq!(hy, 500.0);
+        assert_relative_eq!(hz, 500.0);
+    }
+
+    #[test]
+    fn test_get_child_centre_and_half_length_pos() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        let ((cx, cy, cz), (hx, hy, hz)) =
+            o.get_child_centre_and_half_length_at_pos(true, true, true);
+        assert_relative_eq!(cx, 500.0);
+        assert_relative_eq!(cy, 500.0);
+        assert_relative_eq!(cz, 500.0);
+        assert_relative_eq!(hx, 500.0);
+        assert_relative_eq!(hy, 500.0);
+        assert_relative_eq!(hz, 500.0);
+    }
+
+    #[test]
+    fn test_get_child_centre_and_half_length_partial_pos_off_centre() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (100.0, 200.0, 300.0),
+            1000.0,
+        );
+        let ((cx, cy, cz), (hx, hy, hz)) =
+            o.get_child_centre_and_half_length_at_pos(true, false, true);
+        assert_relative_eq!(cx, 600.0);
+        assert_relative_eq!(cy, -300.0);
+        assert_relative_eq!(cz, 800.0);
+        assert_relative_eq!(hx, 500.0);
+        assert_relative_eq!(hy, 500.0);
+        assert_relative_eq!(hz, 500.0);
+    }
+
+    #[test]
+    fn test_get_child_centre_and_half_length_non_uniform() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed_aabb(
+            (0.0, 0.0, 0.0),
+            (1000.0, 10.0, 1000.0),
+        );
+        assert_eq!(o.data.centre, (500.0, 5.0, 500.0));
+        assert_eq!(o.data.half_lengths, (500.0, 5.0, 500.0));
+        let ((cx, cy, cz), (hx, hy, hz)) =
+            o.get_child_centre_and_half_length_at_pos(true, false, true);
+        assert_relative_eq!(cx, 750.0);
+        assert_relative_eq!(cy, 2.5);
+        assert_relative_eq!(cz, 750.0);
+        assert_relative_eq!(hx, 250.0);
+        assert_relative_eq!(hy, 2.5);
+        assert_relative_eq!(hz, 250.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "octree len underflowed removing 3 from 2")]
+    fn test_clear_data_len_mismatch_panics() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((3.0, 3.0, 3.0));
+        o.data.len = 2;
+        o.clear_data();
+    }
+
+    #[test]
+    fn test_child_bounds_match_each_child() {
+        let mut o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed_aabb(
+            (0, 0, 0),
+            (100, 10, 50),
+        );
+        o.add((1, 1, 1));
+        let bounds = o.child_bounds();
+        for (k, &b) in bounds.iter().enumerate() {
+            let (px, py, pz) = Octree::<()>::get_child_pos_at_idx(k).unwrap();
+            assert_eq!(
+                b,
+                o.get_child_centre_and_half_length_at_pos(px, py, pz)
+            );
+        }
+        assert!(o.is_leaf());
+
+        o = o
+            .with_max_size(1)
+            .with_split_strategy(SplitStrategy::Centroid);
+        o.add((8, 8, 8));
+        o.add((9, 9, 9));
+        o.rebalance();
+        assert_eq!(o.split_point(), (6, 6, 6));
+        let bounds = o.child_bounds();
+        assert_eq!(bounds[0], ((3, 3, 3), (3, 3, 3)));
+        assert_eq!(bounds[7], ((53, 8, 28), (47, 2, 22)));
+        let child = o.get_child(7).unwrap();
+        assert_eq!(bounds[7], (child.centre(), child.half_lengths()));
+    }
+
+    #[test]
+    fn test_rebalance_non_uniform() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed_aabb(
+            (0.0, 0.0, 0.0),
+            (1000.0, 10.0, 1000.0),
+        )
+        .with_max_size(1);
+        o.add((100.0, 1.0, 100.0));
+        o.add((900.0, 9.0, 100.0));
+        o.add((900.0, 1.0, 900.0));
+        o.rebalance();
+        for (node, _) in o.iter_nodes() {
+            for item in &node.data.data {
+                assert!(node.contains_point(*item));
+            }
+        }
+        assert_eq!(o.iter().count(), 3);
+    }
+
+    #[test]
+    fn test_vec_add() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        assert_eq!(o.len(), 0);
+        o.add((123.45, 234.567, 345.678));
+        assert_eq!(o.len(), 1);
+    }
+
+    #[test]
+    fn test_try_add_just_inside() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            10.0,
+        );
+        assert!(o.try_add((10.0, 9.999, -9.999)).is_ok());
+        assert_eq!(o.len(), 1);
+    }
+
+    #[test]
+    fn test_try_add_just_outside() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 2.0, 3.0),
+            10.0,
+        );
+        let err = o.try_add((11.001, 2.0, 3.0)).unwrap_err();
+        assert_eq!(err.point, (11.001, 2.0, 3.0));
+        assert_eq!(err.centre, (1.0, 2.0, 3.0));
+        assert_eq!(err.half_lengths, (10.0, 10.0, 10.0));
+        assert!(o.try_add((1.0, 2.0, -7.0)).is_err());
+        assert_eq!(o.len(), 0);
+    }
+
+    #[test]
+    fn test_array_add() {
+        let mut o = ManagedVecOctree::<[f32; 3], f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add([1.0, 2.0, 3.0]);
+        o.add([-1.0, -2.0, -3.0]);
+        o.rebalance();
+        assert_eq!(o.len(), 2);
+        assert_eq!(o.nearest((1.0, 1.0, 1.0)), Some(&[1.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    fn test_custom_item_add() {
+        #[derive(Debug, PartialEq)]
+        struct Particle {
+            pos: [f32; 3],
+            mass: f32,
+        }
+
+        impl CentredItem<f32> for Particle {
+            fn centre(&self) -> (f32, f32, f32) { self.pos.centre() }
+        }
+
+        let mut o = ManagedVecOctree::<Particle, f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        o.add(Particle {
+            pos: [1.0, 2.0, 3.0],
+            mass: 5.0,
+        });
+        assert_eq!(o.len(), 1);
+        assert_relative_eq!(o.query_radius((1.0, 2.0, 3.0), 0.0)[0].mass, 5.0);
+    }
+
+    #[test]
+    fn test_contains_point_corners_and_centre() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 2.0, 3.0),
+            10.0,
+        );
+        assert!(o.contains_point((1.0, 2.0, 3.0)));
+        for idx in 0..8 {
+            let (px, py, pz) =
+                ManagedVecOctree::<(f32, f32, f32), f32>::get_child_pos_at_idx(
+                    idx,
+                )
+                .unwrap();
+            let corner = (
+                if px { 11.0 } else { -9.0 },
+                if py { 12.0 } else { -8.0 },
+                if pz { 13.0 } else { -7.0 },
+            );
+            assert_eq!(o.contains_point(corner), px && py && pz);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hash_add() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            );
+        assert_eq!(o.len(), 0);
+        o.add((123, (123.45, 234.567, 345.678)));
+        assert_eq!(o.len(), 1);
+    }
+
+    #[test]
+    fn test_btree_add_ordered() {
+        let mut o =
+            ManagedBTreeMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            );
+        assert_eq!(o.len(), 0);
+        assert!(o.add((3, (1.0, 1.0, 1.0))));
+        assert!(o.add((1, (2.0, 2.0, 2.0))));
+        assert!(o.add((2, (3.0, 3.0, 3.0))));
+        assert!(!o.add((1, (4.0, 4.0, 4.0))));
+        assert_eq!(o.len(), 3);
+        let keys: Vec<u32> = o.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let new_tree = || {
+            ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(2)
+        };
+        let mut a = new_tree().with_items(random_points(20));
+        let b = new_tree().with_items(vec![(-5.0, 5.0, 5.0), (5.0, 5.0, -5.0)]);
+        assert_eq!(a.merge(b).unwrap(), 0);
+        assert_eq!(a.len(), 22);
+        assert_eq!(a.iter().count(), 22);
+        assert!(
+            a.iter_nodes()
+                .all(|(node, _)| node.data.get_data().len() <= 2)
+        );
+
+        let c = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_items(vec![(1.0, 1.0, 1.0)]);
+        let err = a.merge(c).unwrap_err();
+        assert_eq!(err.other.len(), 1);
+        assert_eq!(a.len(), 22);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_merge_duplicate_keys() {
+        let mut a =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            );
+        let mut b = a.clone();
+        a.add((1, (1.0, 1.0, 1.0)));
+        b.add((1, (1.0, 1.0, 1.0)));
+        b.add((2, (2.0, 2.0, 2.0)));
+        assert_eq!(a.merge(b).unwrap(), 1);
+        assert_eq!(a.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_rebalances() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4);
+        let points = random_points(5);
+        for &p in &points[..4] {
+            assert!(o.insert(p));
+        }
+        assert!(o.is_leaf());
+        assert!(o.insert(points[4]));
+        assert!(!o.is_leaf());
+        assert_eq!(o.len(), 5);
+        assert!(o.data.get_data().len() <= 4);
+
+        for p in random_points(200) {
+            o.insert(p);
+        }
+        assert!(
+            o.iter_nodes()
+                .all(|(node, _)| node.data.get_data().len() <= 4)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hash_add_duplicate_key() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            );
+        assert!(o.add((1, (1.0, 2.0, 3.0))));
+        assert!(!o.add((1, (4.0, 5.0, 6.0))));
+        assert_eq!(o.len(), 1);
+        assert_eq!(o.iter().next(), Some((&1, &(1.0, 2.0, 3.0))));
+
+        assert!(!o.try_add((1, (7.0, 8.0, 9.0))).unwrap());
+        assert!(o.try_add((2, (7.0, 8.0, 9.0))).unwrap());
+        assert_eq!(o.len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_collapse_keeps_key_duplicated_in_child() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(2)
+            .with_drop_below_size(2);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 2.0, 2.0)));
+        o.add((3, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        assert!(o.get_child(7).unwrap().data.data.contains_key(&1));
+        o.add((1, (-5.0, 5.0, 5.0)));
+        o.remove_key(&2, (2.0, 2.0, 2.0));
+        assert_eq!(o.len(), 3);
+
+        o.collapse();
+        assert_eq!(o.len(), 3);
+        assert_eq!(o.iter().count(), 3);
+        let child = o.get_child(7).unwrap();
+        assert_eq!(child.len(), 1);
+        assert!(child.data.data.contains_key(&1));
+        assert_eq!(o.validate(), Ok(()));
+
+        o.remove_key(&1, (-5.0, 5.0, 5.0));
+        o.collapse();
+        assert!(o.is_leaf());
+        assert_eq!(o.len(), 2);
+        assert_eq!(o.iter().count(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_rebalance_keeps_key_duplicated_in_child() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(4);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 1.0, 1.0)));
+        o.add((3, (3.0, 1.0, 1.0)));
+        o.add((4, (4.0, 1.0, 1.0)));
+        o.add((5, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        assert!(o.get_child(7).unwrap().data.data.contains_key(&1));
+
+        assert!(o.add((1, (5.0, 5.0, 5.0))));
+        assert_eq!(o.len(), 6);
+        o.rebalance();
+        assert_eq!(o.len(), 6);
+        assert_eq!(o.iter().count(), 6);
+        assert_eq!(o.data.data.get(&1), Some(&(5.0, 5.0, 5.0)));
+        assert_eq!(o.validate(), Ok(()));
+
+        let child = o.get_child_mut(7).unwrap();
+        child.rebalance();
+        assert_eq!(o.len(), 6);
+        assert_eq!(o.validate(), Ok(()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_rebalance_one_level_stops_on_rejected_keys() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(2)
+            .with_max_depth(1);
+        for key in 1..=3 {
+            o.add((key, (1.0, 1.0, 1.0)));
+        }
+        o.rebalance();
+        for key in 1..=3 {
+            assert!(o.add((key, (2.0, 2.0, 2.0))));
+        }
+        assert!(!o.rebalance_one_level());
+        assert_eq!(o.data.data.len(), 3);
+        assert_eq!(o.len(), 6);
+        assert_eq!(o.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_remove_item() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        assert_eq!(o.remove_item((2.0, 2.0, 1.0)), Some((2.0, 2.0, 1.0)));
+        assert_eq!(o.len(), 2);
+        assert_eq!(o.get_child(7).unwrap().len(), 1);
+        assert_eq!(o.remove_item((-1.0, -1.0, -1.0)), Some((-1.0, -1.0, -1.0)));
+        assert_eq!(o.len(), 1);
+        assert!(o.remove_item((3.0, 3.0, 3.0)).is_none());
+        assert_eq!(o.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_item_on_split_plane() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((0.0, 0.0, 0.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((1.0, 1.0, 1.0));
+        o.rebalance();
+        assert!(o.data.data.iter().all(|item| item != &(0.0, 0.0, 0.0)));
+        assert_eq!(o.remove_item((0.0, 0.0, 0.0)), Some((0.0, 0.0, 0.0)));
+        assert_eq!(o.len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_remove_key() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 2.0, 2.0)));
+        o.add((3, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        assert_eq!(
+            o.remove_key(&2, (2.0, 2.0, 2.0)),
+            Some((2, (2.0, 2.0, 2.0)))
+        );
+        assert_eq!(o.len(), 2);
+        assert!(o.remove_key(&2, (2.0, 2.0, 2.0)).is_none());
+        assert_eq!(o.len(), 2);
+    }
+
+    #[test]
+    fn test_statistics_unbalanced() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2)
+        .with_max_depth(2);
+        assert_eq!(o.depth(), 0);
+        for i in 0..5_u8 {
+            let offset = f32::from(i);
+            o.add((600.0 + offset, 600.0 + offset, 600.0 + offset));
+        }
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        assert_eq!(o.node_count(), 3);
+        assert_eq!(o.leaf_count(), 1);
+        assert_eq!(o.depth(), 2);
+    }
+
+    #[test]
+    fn test_collapse_after_removal() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2)
+        .with_drop_below_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((3.0, 3.0, 3.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        assert!(o.get_child(7).is_some());
+
+        o.collapse();
+        assert!(o.get_child(7).is_some());
+
+        o.remove_item((1.0, 1.0, 1.0));
+        o.remove_item((2.0, 2.0, 1.0));
+        o.collapse();
+        assert!(o.get_child(7).is_none());
+        assert_eq!(o.len(), 2);
+        assert_eq!(o.data.data.len(), 2);
+
+        o.add((4.0, 4.0, 4.0));
+        o.rebalance();
+        assert!(o.get_child(7).is_some());
+        assert_eq!(o.get_child(7).unwrap().len(), 2);
+        assert_eq!(o.len(), 3);
+    }
+
+    #[test]
+    fn test_try_collapse_node() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(3)
+        .with_drop_below_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
+        o.rebalance();
+        assert!(o.get_child(7).unwrap().is_leaf());
+        assert!(!o.try_collapse_node(7));
+        assert!(!o.try_collapse_node(0));
+        assert!(!o.try_collapse_node(8));
+
+        o.remove_item((1.0, 1.0, 1.0));
+        o.remove_item((2.0, 2.0, 2.0));
+        assert!(o.try_collapse_node(7));
+        assert!(o.get_child(7).is_none());
+        assert!(o.data.data.contains(&(600.0, 600.0, 600.0)));
+        assert_eq!(o.data.data.len(), 3);
+        assert_eq!(o.len(), 3);
+        assert_eq!(o.validate(), Ok(()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_try_collapse_node_keeps_duplicate_key() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(2)
+            .with_drop_below_size(3);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 2.0, 2.0)));
+        o.add((3, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        o.add((1, (-5.0, 5.0, 5.0)));
+        assert_eq!(o.len(), 4);
+
+        assert!(!o.try_collapse_node(7));
+        assert_eq!(o.len(), 4);
+        assert_eq!(o.iter().count(), 4);
+        let child = o.get_child(7).unwrap();
+        assert_eq!(child.len(), 1);
+        assert!(child.data.data.contains_key(&1));
+        assert!(o.data.data.contains_key(&2));
+
+        o.remove_key(&1, (-5.0, 5.0, 5.0));
+        assert!(o.try_collapse_node(7));
+        assert!(o.is_leaf());
+        assert_eq!(o.len(), 3);
+        assert_eq!(o.iter().count(), 3);
+    }
+
+    /// Generates `n` deterministic pseudo-random points within 1000 of the
+    /// origin on each axis.
+    fn random_points(n: usize) -> Vec<(f32, f32, f32)> {
+        let mut seed: u32 = 12345;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (f32::from((seed >> 16) as u16) / f32::from(u16::MAX))
+                .mul_add(2000.0, -1000.0)
+        };
+        (0..n).map(|_| (next(), next(), next())).collect()
+    }
+
+    #[test]
+    fn test_from_items_10k() {
+        let points = random_points(10_000);
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(8)
+        .with_items(points.iter().copied());
+        assert_eq!(o.len(), 10_000);
+        assert_eq!(o.iter().count(), 10_000);
+        assert!(o.depth() > 1);
+        assert!(o.iter_nodes().all(|(node, _)| node.data.data.len() <= 8));
+        assert_eq!(o.validate(), Ok(()));
+        for point in &points {
+            assert!(o.query_radius(*point, 0.0).contains(&point));
+        }
+    }
+
+    #[test]
+    fn test_from_items_coincident_points() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::from_items(
+            (0.0, 0.0, 0.0),
+            1000.0,
+            vec![(1.0, 1.0, 1.0); 5],
+        );
+        assert_eq!(o.len(), 5);
+        assert_eq!(o.iter().count(), 5);
+    }
+
+    #[test]
+    fn test_rebalance_recursive_cluster() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(3);
+        for (x, y, z) in random_points(200) {
+            o.add((x / 100.0 + 600.0, y / 100.0 + 600.0, z / 100.0 + 600.0));
+        }
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        assert!(o.depth() > 2);
+        assert_eq!(o.len(), 201);
+        assert_eq!(o.iter().count(), 201);
+        assert!(o.iter_nodes().all(|(node, _)| node.data.data.len() <= 3));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 2.0, 3.0),
+            1000.0,
+        )
+        .with_max_size(2)
+        .with_drop_below_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((3.0, 3.0, 3.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        o.clear();
+        assert_eq!(o.len(), 0);
+        assert!(o.is_empty());
+        assert!((0..8).all(|i| o.get_child(i).is_none()));
+        assert_eq!(o.data.centre, (1.0, 2.0, 3.0));
+        assert_eq!(o.data.half_lengths, (1000.0, 1000.0, 1000.0));
+        assert_eq!(o.data.max_size, 2);
+        assert_eq!(o.data.drop_below_size, 2);
+    }
+
+    #[test]
+    fn test_clear_resets_split_and_meta() {
+        let new = || {
+            ManagedVecOctree::<(f32, f32, f32), f32, u32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1)
+            .with_split_strategy(SplitStrategy::Centroid)
+        };
+        let mut o = new();
+        o.add((100.0, 100.0, 100.0));
+        o.add((200.0, 200.0, 200.0));
+        o.add((-30.0, -30.0, -30.0));
+        o.rebalance();
+        *o.meta_mut() = 7;
+        assert!(o.data.split.is_some());
+
+        o.clear();
+        assert_eq!(o, new());
+    }
+
+    #[test]
+    fn test_fold_nodes_reads_managed_data() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4)
+        .with_items(random_points(100));
+        let stored = o.fold_nodes(0, |count, node| {
+            count + node.get_data().get_data().len()
+        });
+        assert_eq!(stored, o.len());
+    }
+
+    #[test]
+    fn test_rebalance_max_2() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        assert_eq!(o.data.data.len(), 1);
+        assert!(o.get_child_at_pos(true, true, true).is_some());
+        assert!(o.get_child_at_pos(false, false, false).is_none());
+        assert_eq!(
+            o.get_child_at_pos(true, true, true)
+                .unwrap()
+                .data
+                .data
+                .len(),
+            2
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (100.0, 200.0, 300.0),
+            1000.0,
+        )
+        .with_max_size(2)
+        .with_drop_below_size(2);
+        o.add((101.5, 201.5, 301.5));
+        o.add((102.25, 202.0, 301.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((500.0, -300.0, 900.0));
+        o.rebalance();
+
+        let json = serde_json::to_string(&o).unwrap();
+        let r: ManagedVecOctree<(f32, f32, f32), f32> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(r, o);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Sphere {
+        centre: (f32, f32, f32),
+        radius: f32,
+    }
+
+    impl CentredItem<f32> for Sphere {
+        fn centre(&self) -> (f32, f32, f32) { self.centre }
+
+        fn half_extents(&self) -> (f32, f32, f32) {
+            (self.radius, self.radius, self.radius)
+        }
+    }
+
+    fn sphere(x: f32, radius: f32) -> Sphere {
+        Sphere {
+            centre: (x, x, x),
+            radius,
+        }
+    }
 
     #[test]
-    fn test_with_drop_below_size() {
-        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+    fn test_loose_straddling_item_stays_in_parent() {
+        let mut o = ManagedVecOctree::<Sphere, f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
         )
-        .with_drop_below_size(3);
-        assert_eq!(o.data.drop_below_size, 3);
+        .with_max_size(1)
+        .with_looseness(0.5);
+        o.add(sphere(1.0, 300.0));
+        o.add(sphere(5.0, 1.0));
+        o.add(sphere(-5.0, 1.0));
+        o.rebalance();
+        assert_eq!(o.data.data, vec![sphere(1.0, 300.0)]);
+        assert_eq!(o.get_child(7).unwrap().data.data, vec![sphere(5.0, 1.0)]);
+        assert_eq!(o.get_child(0).unwrap().data.data, vec![sphere(-5.0, 1.0)]);
+        assert_eq!(o.len(), 3);
+    }
+
+    fn oversized_spheres() -> ManagedVecOctree<Sphere, f32> {
+        let mut o = ManagedVecOctree::<Sphere, f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add(sphere(1.0, 800.0));
+        o.add(sphere(-1.0, 800.0));
+        o.add(sphere(2.0, 800.0));
+        o
     }
 
     #[test]
-    #[should_panic]
-    fn test_with_drop_below_size_0_panics() {
-        let _ = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+    fn test_rebalance_one_level_stops_on_oversized_items() {
+        let mut o = oversized_spheres();
+        assert!(!o.rebalance_one_level());
+        assert_eq!(o.children().count(), 0);
+        assert_eq!(o.len(), 3);
+
+        o.add(sphere(3.0, 1.0));
+        o.add(sphere(-3.0, 1.0));
+        let mut steps = 0;
+        while o.rebalance_one_level() {
+            steps += 1;
+            assert!(steps <= 1);
+        }
+        assert_eq!(o.data.data.len(), 3);
+        assert_eq!(o.len(), 5);
+    }
+
+    #[test]
+    fn test_validate_ignores_oversized_items() {
+        let mut o = oversized_spheres();
+        o.rebalance();
+        assert_eq!(o.validate(), Ok(()));
+        o.add(sphere(3.0, 1.0));
+        o.add(sphere(4.0, 1.0));
+        assert!(o.validate().is_err());
+        o.rebalance();
+        assert_eq!(o.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_looseness_lets_item_cross_split_plane() {
+        let build = |looseness| {
+            ManagedVecOctree::<Sphere, f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(0)
+            .with_max_depth(1)
+            .with_looseness(looseness)
+            .with_items(vec![sphere(1.0, 200.0)])
+        };
+        assert!(build(0.0).get_child(7).is_none());
+        let o = build(0.5);
+        assert_relative_eq!(o.looseness(), 0.5);
+        assert!(o.is_node_empty());
+        assert_eq!(o.get_child(7).unwrap().len(), 1);
+        let (min, max) = o.get_child(7).unwrap().loose_bounds();
+        assert_eq!((min.0, max.0), (-250.0, 1250.0));
+    }
+
+    #[test]
+    fn test_octant_index_matches_placement() {
+        let points = [
+            (1.0, 1.0, 1.0),
+            (-1.0, 2.0, -3.0),
+            (4.0, -5.0, 6.0),
+            (0.0, 0.0, 7.0),
+            (-8.0, -9.0, 0.0),
+        ];
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
         )
-        .with_drop_below_size(0);
+        .with_max_size(0)
+        .with_max_depth(1);
+        for &p in &points {
+            o.add(p);
+        }
+        o.rebalance();
+        for &p in &points {
+            let idx = octant_index(p, o.centre());
+            assert!(o.get_child(idx).unwrap().data.data.contains(&p));
+        }
+        assert_eq!(octant_index((0.0, 0.0, 7.0), (0.0, 0.0, 0.0)), 1);
     }
 
     #[test]
-    fn test_with_max_size() {
-        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+    fn test_remove_subtree_updates_len() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
         )
-        .with_max_size(3);
-        assert_eq!(o.data.max_size, 3);
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
+        o.rebalance();
+        assert_eq!(o.len(), 4);
+
+        let removed = o.remove_subtree(&[7, 0]).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(o.len(), 3);
+        assert_eq!(o.get_child(7).unwrap().len(), 1);
+
+        let removed = o.remove_subtree(&[0]).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(o.len(), 1);
+        assert_eq!(o.validate(), Ok(()));
+
+        assert!(o.remove_subtree(&[]).is_none());
+        assert!(o.remove_subtree(&[0]).is_none());
+        assert_eq!(o.len(), 1);
     }
 
     #[test]
-    fn test_get_child_centre_and_half_length_neg() {
-        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+    fn test_remove_subtree_updates_every_ancestor() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
-        );
-        let ((cx, cy, cz), half_length) =
-            o.get_child_centre_and_half_length_at_pos(false, false, false);
-        assert_relative_eq!(cx, -500.0);
-        assert_relative_eq!(cy, -500.0);
-        assert_relative_eq!(cz, -500.0);
-        assert_relative_eq!(half_length, 500.0);
+        )
+        .with_max_size(1);
+        o.add((-1.0, -1.0, -1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((2.0, 2.0, 2.0));
+        o.rebalance();
+        let path = o.add_tracked((1.0, 1.0, 1.0));
+        assert!(path.len() >= 3);
+        let before: Vec<usize> = (0..path.len())
+            .map(|depth| o.get_by_path(&path[..depth]).unwrap().len())
+            .collect();
+
+        let (&last, parent) = path.split_last().unwrap();
+        let mut stale = o.clone();
+        stale.get_by_path_mut(parent).unwrap().remove_child(last);
+        assert!(stale.validate().is_err());
+
+        let removed = o.remove_subtree(&path).unwrap();
+        assert!(!removed.is_empty());
+        for (depth, len) in before.into_iter().enumerate() {
+            assert_eq!(
+                o.get_by_path(&path[..depth]).unwrap().len(),
+                len - removed.len()
+            );
+        }
+        assert_eq!(o.validate(), Ok(()));
     }
 
     #[test]
-    fn test_get_child_centre_and_half_length_pos() {
-        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+    fn test_add_tracked_path_holds_item() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        let points = [
+            (1.0, 1.0, 1.0),
+            (600.0, 600.0, 600.0),
+            (-1.0, -1.0, -1.0),
+            (3.0, 2.0, 1.0),
+            (-300.0, 200.0, 100.0),
+        ];
+        for &p in &points {
+            let path = o.add_tracked(p);
+            let node = o.get_by_path(&path).unwrap();
+            assert!(node.contains_point(p));
+            assert!(node.data.data.contains(&p));
+        }
+        assert_eq!(o.len(), points.len());
+        assert!(!o.add_tracked((5.0, 5.0, 5.0)).is_empty());
+    }
+
+    #[test]
+    fn test_recompute_meta_counts() {
+        let mut o =
+            ManagedVecOctree::<(f32, f32, f32), f32, usize>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
+        o.add((-300.0, 200.0, 100.0));
+        o.rebalance();
+        assert_eq!(*o.meta(), 0);
+
+        o.recompute_meta(|items, children| {
+            items.len() + children.iter().copied().sum::<usize>()
+        });
+        assert_eq!(*o.meta(), o.len());
+        for (node, _) in o.iter_nodes() {
+            let children: usize = node.children().map(|(_, c)| *c.meta()).sum();
+            assert_eq!(*node.meta(), node.data.data.len() + children);
+            assert_eq!(*node.meta(), node.len());
+        }
+
+        *o.meta_mut() = 0;
+        assert_eq!(*o.meta(), 0);
+    }
+
+    #[test]
+    fn test_root_octant_histogram() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
         );
-        let ((cx, cy, cz), half_length) =
-            o.get_child_centre_and_half_length_at_pos(true, true, true);
-        assert_relative_eq!(cx, 500.0);
-        assert_relative_eq!(cy, 500.0);
-        assert_relative_eq!(cz, 500.0);
-        assert_relative_eq!(half_length, 500.0);
+        for i in 1..=5_u8 {
+            let f = f32::from(i);
+            o.add((f, f, f));
+        }
+        o.add((-1.0, -1.0, -1.0));
+        o.add((0.0, 0.0, 2.0));
+        o.add((3.0, -3.0, 0.0));
+        assert_eq!(o.root_octant_histogram(), [1, 1, 0, 0, 1, 0, 0, 5]);
+        assert_eq!(o.len(), 8);
+        assert!(o.get_child(7).is_none());
     }
 
     #[test]
-    fn test_get_child_centre_and_half_length_partial_pos_off_centre() {
-        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
-            (100.0, 200.0, 300.0),
+    fn test_add_subtree_updates_len() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
             1000.0,
+        )
+        .with_max_size(1);
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
+        o.rebalance();
+
+        let (centre, half_lengths) =
+            o.get_child_centre_and_half_length_at_pos(true, true, true);
+        let mut child = ManagedVecOctree::new_managed_with_half_lengths(
+            centre,
+            half_lengths,
         );
-        let ((cx, cy, cz), half_length) =
-            o.get_child_centre_and_half_length_at_pos(true, false, true);
-        assert_relative_eq!(cx, 600.0);
-        assert_relative_eq!(cy, -300.0);
-        assert_relative_eq!(cz, 800.0);
-        assert_relative_eq!(half_length, 500.0);
+        child.add((1.0, 1.0, 1.0));
+        child.add((600.0, 600.0, 600.0));
+        child.add((3.0, 2.0, 1.0));
+        o.add_subtree(&[7], child).unwrap();
+        assert_eq!(o.len(), 5);
+        assert_eq!(o.get_child(7).unwrap().len(), 3);
+
+        let removed = o.remove_subtree(&[0]).unwrap();
+        assert_eq!(o.len(), 3);
+        assert!(matches!(
+            o.add_subtree(&[5, 0], removed.clone()),
+            Err(AddChildError::MissingParent)
+        ));
+        assert!(matches!(
+            o.add_subtree(&[7], removed.clone()),
+            Err(AddChildError::AlreadyAdded)
+        ));
+        assert!(matches!(
+            o.add_subtree(&[], removed.clone()),
+            Err(AddChildError::MissingParent)
+        ));
+        assert_eq!(o.len(), 3);
+        o.add_subtree(&[0], removed).unwrap();
+        assert_eq!(o.len(), 5);
+        o.rebalance();
+        assert_eq!(o.validate(), Ok(()));
     }
 
     #[test]
-    fn test_vec_add() {
+    fn test_add_subtree_updates_every_ancestor() {
         let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
+        )
+        .with_max_size(1);
+        o.add((-1.0, -1.0, -1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((2.0, 2.0, 2.0));
+        o.rebalance();
+        let path = o.add_tracked((1.0, 1.0, 1.0));
+        assert!(path.len() >= 3);
+        let before: Vec<usize> = (0..path.len())
+            .map(|depth| o.get_by_path(&path[..depth]).unwrap().len())
+            .collect();
+        let removed = o.remove_subtree(&path).unwrap();
+
+        let (&last, parent) = path.split_last().unwrap();
+        let mut stale = o.clone();
+        stale
+            .get_by_path_mut(parent)
+            .unwrap()
+            .add_child(last, removed.clone())
+            .unwrap();
+        assert!(stale.validate().is_err());
+
+        o.add_subtree(&path, removed).unwrap();
+        for (depth, len) in before.into_iter().enumerate() {
+            assert_eq!(o.get_by_path(&path[..depth]).unwrap().len(), len);
+        }
+        assert_eq!(o.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_distance_to_bounds() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed_aabb(
+            (-10.0, -20.0, -30.0),
+            (10.0, 20.0, 30.0),
         );
-        assert_eq!(o.len(), 0);
-        o.add((123.45, 234.567, 345.678));
-        assert_eq!(o.len(), 1);
+        assert_relative_eq!(o.distance_to_bounds((0.0, 0.0, 0.0)), 10.0);
+        assert_relative_eq!(o.distance_to_bounds((0.0, 15.0, 0.0)), 5.0);
+        assert_relative_eq!(o.distance_to_bounds((10.0, 0.0, 0.0)), 0.0);
+        assert_relative_eq!(o.distance_to_bounds((0.0, 0.0, -30.0)), 0.0);
+        assert_relative_eq!(o.distance_to_bounds((12.0, 0.0, 0.0)), -2.0);
+        assert_relative_eq!(o.distance_to_bounds((12.0, -25.0, 0.0)), -5.0);
+
+        let cube = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed(
+            (100, 100, 100),
+            50,
+        );
+        assert_eq!(cube.distance_to_bounds((100, 100, 100)), 50);
+        assert_eq!(cube.distance_to_bounds((150, 100, 100)), 0);
     }
 
     #[test]
-    fn test_hash_add() {
-        let mut o =
-            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+    fn test_split_threshold_avoids_thrashing() {
+        let new_tree = || {
+            ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
                 (0.0, 0.0, 0.0),
                 1000.0,
-            );
-        assert_eq!(o.len(), 0);
-        o.add((123, (123.45, 234.567, 345.678)));
-        assert_eq!(o.len(), 1);
+            )
+            .with_max_size(2)
+            .with_drop_below_size(2)
+        };
+        let mut plain = new_tree();
+        let mut o = new_tree().with_split_threshold(4);
+        assert_eq!(o.split_threshold(), 4);
+        assert_eq!(plain.split_threshold(), 2);
+        for tree in [&mut plain, &mut o] {
+            tree.insert((1.0, 1.0, 1.0));
+            tree.insert((-1.0, -1.0, -1.0));
+        }
+        for _ in 0..3 {
+            plain.insert((2.0, 2.0, 2.0));
+            o.insert((2.0, 2.0, 2.0));
+            assert!(plain.node_count() > 1);
+            assert_eq!(o.node_count(), 1);
+            plain.remove_item((2.0, 2.0, 2.0));
+            o.remove_item((2.0, 2.0, 2.0));
+            plain.collapse();
+            o.collapse();
+            assert_eq!(o.node_count(), 1);
+        }
+
+        o.insert((3.0, 3.0, 3.0));
+        o.insert((-3.0, -3.0, -3.0));
+        assert_eq!(o.node_count(), 1);
+        o.insert((4.0, 4.0, 4.0));
+        assert!(o.node_count() > 1);
+        assert!(o.data.data.len() <= 2);
+        assert_eq!(o.len(), 5);
+        assert_eq!(o.validate(), Ok(()));
     }
 
     #[test]
-    fn test_rebalance_max_2() {
+    fn test_item_belongs_here() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            10.0,
+        )
+        .with_max_size(1);
+        assert!(o.item_belongs_here(&(1.0, -2.0, 3.0)));
+        assert!(o.item_belongs_here(&(10.0, 10.0, 10.0)));
+        assert!(!o.item_belongs_here(&(-10.0, 0.0, 0.0)));
+        assert!(!o.item_belongs_here(&(11.0, 0.0, 0.0)));
+
+        o.add((1.0, 1.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        let child = o.get_child(0).unwrap();
+        assert!(child.item_belongs_here(&(-1.0, -1.0, -1.0)));
+        assert!(child.item_belongs_here(&(0.0, 0.0, 0.0)));
+        assert!(!child.item_belongs_here(&(1.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_for_each_item_mut_visits_all() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        for i in -10..10_i8 {
+            let f = f32::from(i);
+            o.add((f * 10.0, f * 5.0, f));
+        }
+        o.rebalance();
+        assert!(o.depth() > 1);
+
+        let mut visited = 0;
+        o.for_each_item_mut(|item| {
+            item.0 += 1.0;
+            visited += 1;
+        });
+        assert_eq!(visited, 20);
+        assert_eq!(o.len(), 20);
+        for i in -10..10_i8 {
+            let f = f32::from(i);
+            let point = (f32::from(i * 10 + 1), f * 5.0, f);
+            assert!(o.iter().any(|item| *item == point));
+        }
+    }
+
+    #[test]
+    fn test_leaves_with_bounds() {
         let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
             (0.0, 0.0, 0.0),
             1000.0,
         )
         .with_max_size(2);
+        for i in -10..10_i8 {
+            let f = f32::from(i);
+            o.add((f * 40.0, f * 30.0, f * 20.0));
+        }
+        o.rebalance();
+        assert!(o.depth() > 2);
+
+        let leaves: Vec<_> = o.leaves().collect();
+        assert_eq!(leaves.len(), o.leaf_count());
+        for (centre, half_lengths, data) in &leaves {
+            for item in *data {
+                assert!((item.0 - centre.0).abs() <= half_lengths.0);
+            }
+        }
+
+        let leaf = o.locate((40.0, 30.0, 20.0)).unwrap();
+        assert!(leaf.is_leaf());
+        assert!(leaves.iter().any(|(centre, half_lengths, data)| {
+            *centre == leaf.centre()
+                && *half_lengths == leaf.half_lengths()
+                && data.contains(&(40.0, 30.0, 20.0))
+        }));
+
+        let single: Vec<_> =
+            ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+                (1.0, 2.0, 3.0),
+                4.0,
+            )
+            .leaves()
+            .map(|(centre, half_lengths, _)| (centre, half_lengths))
+            .collect();
+        assert_eq!(single, vec![((1.0, 2.0, 3.0), (4.0, 4.0, 4.0))]);
+    }
+
+    #[test]
+    fn test_prune_empty_after_retain() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
         o.add((1.0, 1.0, 1.0));
-        o.add((2.0, 2.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((600.0, 600.0, 600.0));
         o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
         o.rebalance();
-        assert_eq!(o.data.data.len(), 1);
-        assert!(o.get_child_at_pos(true, true, true).is_some());
-        assert!(o.get_child_at_pos(false, false, false).is_none());
+        assert!(o.get_child(7).is_some());
+        assert!(o.get_child(0).is_some());
+        let nodes = o.node_count();
+        let child_nodes = o.get_child(7).unwrap().node_count();
+        assert!(child_nodes > 1);
+
+        o.retain(|item| item.0 < 0.0);
+        assert_eq!(o.node_count(), nodes);
+        assert_eq!(o.prune_empty(), child_nodes);
+        assert!(o.get_child(7).is_none());
+        assert!(o.get_child(0).is_some());
+        assert_eq!(o.node_count(), nodes - child_nodes);
+        assert_eq!(o.len(), 2);
+        assert_eq!(o.prune_empty(), 0);
+    }
+
+    #[test]
+    fn test_add_routed_uses_existing_children() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        let root_len = o.data.data.len();
+        let target = o.locate((2.0, 2.0, 2.0)).unwrap();
+        assert!(target.depth() < o.depth());
+        let target_centre = target.centre();
+        assert!(target_centre != o.centre());
+
+        assert!(o.add_routed((2.0, 2.0, 2.0)));
+        assert_eq!(o.data.data.len(), root_len);
+        assert_eq!(o.len(), 4);
+        assert_eq!(o.get_child(7).unwrap().len(), 3);
+        let target = o.locate((2.0, 2.0, 2.0)).unwrap();
+        assert_eq!(target.centre(), target_centre);
+        assert!(target.data.data.contains(&(2.0, 2.0, 2.0)));
+
+        let nodes = o.node_count();
+        assert!(o.add_routed((-500.0, 500.0, -500.0)));
+        assert_eq!(o.node_count(), nodes);
+        assert!(o.data.data.contains(&(-500.0, 500.0, -500.0)));
+        assert_eq!(o.data.data.len(), root_len + 1);
+    }
+
+    #[test]
+    fn test_rebalance_single_child() {
+        let mut o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed(
+            (0, 0, 0),
+            64,
+        )
+        .with_max_size(1);
+        o.add((-10, -10, -10));
+        o.add((-10, 10, 10));
+        o.add((10, 10, 10));
+        o.rebalance();
+        assert!(o.get_child(0).unwrap().is_leaf());
+        let untouched = o.get_child(3).unwrap().clone();
+        let root_items = o.data.data.clone();
+
+        for i in 1..=10 {
+            assert!(o.add_routed((-3 * i, -5 * i, -2 * i)));
+        }
+        assert_eq!(o.get_child(0).unwrap().data.data.len(), 11);
+
+        let child = o.get_child_mut(0).unwrap();
+        child.rebalance();
+        assert!(!child.is_leaf());
+        assert!(child.data.data.len() <= 1);
+        assert_eq!(child.len(), 11);
+        assert_eq!(child.validate(), Ok(()));
+
+        assert_eq!(o.get_child(3), Some(&untouched));
+        assert_eq!(o.data.data, root_items);
+        assert_eq!(o.len(), 13);
+        assert_eq!(o.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_centroid_split_is_shallower() {
+        let build = |strategy| {
+            let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1)
+            .with_split_strategy(strategy);
+            for i in 0..64_u8 {
+                let offset =
+                    |bits: u8| f32::from(900 + 3 * u16::from(bits & 3));
+                o.add((offset(i), offset(i >> 2), offset(i >> 4)));
+            }
+            o.rebalance();
+            o
+        };
+        let midpoint = build(SplitStrategy::Midpoint);
+        let centroid = build(SplitStrategy::Centroid);
+        assert_eq!(midpoint.split_point(), midpoint.centre());
+        assert_eq!(centroid.split_point(), (904.5, 904.5, 904.5));
+        assert!(centroid.depth() < midpoint.depth());
+        assert_eq!(centroid.depth(), 2);
+        assert_eq!(centroid.validate(), Ok(()));
+
+        for item in midpoint.iter() {
+            let node = centroid.locate(*item).unwrap();
+            assert!(node.data.data.contains(item));
+        }
         assert_eq!(
-            o.get_child_at_pos(true, true, true)
-                .unwrap()
-                .data
-                .data
+            centroid
+                .query_aabb((899.0, 899.0, 899.0), (904.0, 904.0, 904.0))
                 .len(),
-            2
+            8
+        );
+    }
+
+    #[test]
+    fn test_rebalance_is_deterministic() {
+        let build = || {
+            let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(3);
+            for i in 0..8_u8 {
+                let sign = |bit| if i & bit == 0 { -1.0 } else { 1.0 };
+                for j in 1..=2_u8 {
+                    let f = f32::from(j) * 10.0;
+                    o.add((sign(4) * f, sign(2) * f, sign(1) * f));
+                }
+            }
+            o.rebalance();
+            o
+        };
+        let a = build();
+        assert_eq!(a, build());
+        for idx in 0..7 {
+            assert!(a.get_child(idx).is_some());
+        }
+        assert!(a.get_child(7).is_none());
+        assert_eq!(a.data.data.len(), 2);
+    }
+
+    #[test]
+    fn test_take_subtree_is_standalone() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        let child_len = o.get_child(7).unwrap().len();
+        assert_eq!(child_len, 3);
+
+        let mut taken = o.take_subtree(7).unwrap();
+        assert!(o.get_child(7).is_none());
+        assert_eq!(o.len(), 1);
+        assert_eq!(o.validate(), Ok(()));
+        assert_eq!(taken.len(), 3);
+        assert_eq!(taken.centre(), (500.0, 500.0, 500.0));
+        assert_eq!(taken.validate(), Ok(()));
+        assert_eq!(
+            taken.nearest((590.0, 590.0, 590.0)),
+            Some(&(600.0, 600.0, 600.0))
         );
+
+        taken.add((700.0, 700.0, 700.0));
+        taken.rebalance();
+        assert_eq!(taken.len(), 4);
+        assert_eq!(taken.validate(), Ok(()));
+        assert!(o.take_subtree(7).is_none());
+    }
+
+    #[test]
+    fn test_rebalance_stops_at_float_precision() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 1.0, 1.0),
+            1.0,
+        )
+        .with_max_size(1);
+        let mut points = Vec::new();
+        for i in 0..20_u8 {
+            let offset = f32::from(i) * f32::EPSILON;
+            points.push((1.0 + offset, 1.0 - offset, 1.0));
+            points.push((1.0 + offset, 1.0 - offset, 1.0));
+        }
+        for &point in &points {
+            o.add(point);
+        }
+        o.rebalance();
+        assert_eq!(o.len(), points.len());
+        assert_eq!(o.iter().count(), points.len());
+        assert_eq!(o.validate(), Ok(()));
+        for &point in &points {
+            assert!(o.remove_item(point).is_some());
+        }
+        assert_eq!(o.len(), 0);
     }
 }
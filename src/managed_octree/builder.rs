@@ -0,0 +1,272 @@
+use super::{ManagedOctree, ManagedOctreeData, SplitStrategy};
+use crate::Octree;
+use core::{
+    marker::PhantomData,
+    ops::{Add, Div, Sub},
+};
+use len_trait::{Empty, Len};
+use num::One;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Returned by `OctreeBuilder::build` when the configured sizes are invalid.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// `drop_below_size` was set to 0, so `collapse` could never merge nodes.
+    ZeroDropBelowSize,
+    /// `drop_below_size` exceeds `max_size`, so `collapse` would merge nodes
+    /// that `rebalance` had just split.
+    DropBelowSizeExceedsMaxSize {
+        drop_below_size: usize,
+        max_size: usize,
+    },
+}
+
+/// Configures the bounds and sizes of a `ManagedOctree`, validating them
+/// together when it's built.
+#[derive(Clone, Debug)]
+pub struct OctreeBuilder<D, S> {
+    centre: (S, S, S),
+    half_length: S,
+    max_size: usize,
+    drop_below_size: usize,
+    max_depth: u32,
+    data: PhantomData<D>,
+}
+
+impl<D, S> Default for OctreeBuilder<D, S>
+where
+    S: Default + One,
+{
+    fn default() -> Self {
+        Self {
+            centre: (S::default(), S::default(), S::default()),
+            half_length: S::one(),
+            max_size: 1,
+            drop_below_size: 1,
+            max_depth: u32::MAX,
+            data: PhantomData,
+        }
+    }
+}
+
+impl<D, S> OctreeBuilder<D, S>
+where
+    D: Default + Empty + Len,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+{
+    /// Creates a builder with the same defaults as `new_managed`, centred on
+    /// the origin with a half length of one.
+    #[must_use]
+    pub fn new() -> Self { Self::default() }
+
+    /// Set the centre of the tree.
+    #[must_use]
+    pub const fn centre(mut self, centre: (S, S, S)) -> Self {
+        self.centre = centre;
+        self
+    }
+
+    /// Set the half length of the tree along every axis.
+    #[must_use]
+    pub const fn half_length(mut self, half_length: S) -> Self {
+        self.half_length = half_length;
+        self
+    }
+
+    /// Set `max_size`
+    #[must_use]
+    pub const fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Set `drop_below_size`
+    #[must_use]
+    pub const fn drop_below_size(mut self, drop_below_size: usize) -> Self {
+        self.drop_below_size = drop_below_size;
+        self
+    }
+
+    /// Set `max_depth`
+    #[must_use]
+    pub const fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Builds an empty tree with this configuration.
+    ///
+    /// # Errors
+    /// Returns an error if `drop_below_size` is 0 or exceeds `max_size`.
+    pub fn build(self) -> Result<ManagedOctree<D, S>, BuildError> {
+        if self.drop_below_size == 0 {
+            return Err(BuildError::ZeroDropBelowSize);
+        }
+        if self.drop_below_size > self.max_size {
+            return Err(BuildError::DropBelowSizeExceedsMaxSize {
+                drop_below_size: self.drop_below_size,
+                max_size: self.max_size,
+            });
+        }
+        let h = self.half_length;
+        Ok(Octree::new_with_data(ManagedOctreeData {
+            centre: self.centre,
+            half_lengths: (h, h, h),
+            max_size: self.max_size,
+            drop_below_size: self.drop_below_size,
+            max_depth: self.max_depth,
+            ..ManagedOctreeData::default()
+        }))
+    }
+}
+
+/// The bounds and sizes of a `ManagedOctree` without its contents, as
+/// returned by `config`, for creating empty trees configured identically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OctreeConfig<S> {
+    pub centre: (S, S, S),
+    pub half_lengths: (S, S, S),
+    pub max_size: usize,
+    pub split_threshold: usize,
+    pub drop_below_size: usize,
+    pub max_depth: u32,
+    pub looseness: S,
+    pub split_strategy: SplitStrategy,
+}
+
+impl<S> OctreeConfig<S>
+where
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+{
+    /// Creates an empty tree with this configuration.
+    #[must_use]
+    pub fn build_empty<D, M>(&self) -> ManagedOctree<D, S, M>
+    where
+        D: Default + Empty + Len,
+        M: Default,
+    {
+        Octree::new_with_data(ManagedOctreeData {
+            centre: self.centre,
+            half_lengths: self.half_lengths,
+            max_size: self.max_size,
+            split_threshold: self.split_threshold,
+            drop_below_size: self.drop_below_size,
+            max_depth: self.max_depth,
+            looseness: self.looseness,
+            split_strategy: self.split_strategy,
+            ..ManagedOctreeData::default()
+        })
+    }
+}
+
+impl<D, S, M> ManagedOctree<D, S, M>
+where
+    D: Default + Empty + Len,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Gets this node's configuration, ignoring its items and children.
+    #[must_use]
+    pub const fn config(&self) -> OctreeConfig<S> {
+        OctreeConfig {
+            centre: self.data.centre,
+            half_lengths: self.data.half_lengths,
+            max_size: self.data.max_size,
+            split_threshold: self.split_threshold(),
+            drop_below_size: self.data.drop_below_size,
+            max_depth: self.data.max_depth,
+            looseness: self.data.looseness,
+            split_strategy: self.data.split_strategy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::ManagedVecOctree, BuildError, OctreeBuilder};
+    use len_trait::Len;
+
+    type Builder = OctreeBuilder<Vec<(f32, f32, f32)>, f32>;
+
+    #[test]
+    fn test_build_matches_new_managed() {
+        let built = Builder::new()
+            .centre((1.0, 2.0, 3.0))
+            .half_length(100.0)
+            .max_size(8)
+            .drop_below_size(4)
+            .max_depth(3)
+            .build()
+            .unwrap();
+        let manual = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (1.0, 2.0, 3.0),
+            100.0,
+        )
+        .with_max_size(8)
+        .with_drop_below_size(4)
+        .with_max_depth(3);
+        assert_eq!(built, manual);
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_sizes() {
+        assert_eq!(
+            Builder::new().drop_below_size(0).build(),
+            Err(BuildError::ZeroDropBelowSize)
+        );
+        assert_eq!(
+            Builder::new().max_size(2).drop_below_size(3).build(),
+            Err(BuildError::DropBelowSizeExceedsMaxSize {
+                drop_below_size: 3,
+                max_size: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed_aabb(
+            (-10.0, -20.0, -30.0),
+            (10.0, 20.0, 30.0),
+        )
+        .with_max_size(4)
+        .with_split_threshold(6)
+        .with_drop_below_size(2)
+        .with_max_depth(5)
+        .with_looseness(0.5);
+        for i in 0..10_u8 {
+            o.insert((f32::from(i), 0.0, 0.0));
+        }
+
+        let config = o.config();
+        let empty: ManagedVecOctree<(f32, f32, f32), f32> =
+            config.build_empty();
+        assert_eq!(empty.config(), config);
+        assert_eq!(empty.centre(), (0.0, 0.0, 0.0));
+        assert_eq!(empty.half_lengths(), (10.0, 20.0, 30.0));
+        assert_eq!(empty.max_size(), 4);
+        assert_eq!(empty.split_threshold(), 6);
+        assert_eq!(empty.drop_below_size(), 2);
+        assert_eq!(empty.max_depth(), 5);
+        assert_relative_eq!(empty.looseness(), 0.5);
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.node_count(), 1);
+    }
+}
@@ -0,0 +1,194 @@
+use super::{CentredItem, ManagedHashMapOctree, ManagedOctreeData, grow_len};
+use alloc::vec::Vec;
+use core::{
+    hash::Hash,
+    ops::{Add, Div, Sub},
+};
+use num::One;
+use std::collections::hash_map;
+
+/// A view into a single entry of a `ManagedHashMapOctree`, which may either
+/// be vacant or occupied. Returned by `entry`.
+pub enum Entry<'a, K, V> {
+    Occupied(hash_map::OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// A view into a vacant entry of a `ManagedHashMapOctree`, which keeps the
+/// tree's `len` up to date when a value is inserted.
+pub struct VacantEntry<'a, K, V> {
+    entry: hash_map::VacantEntry<'a, K, V>,
+    lens: Vec<&'a mut usize>,
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    /// Gets the key that would be used when inserting a value.
+    #[must_use]
+    pub fn key(&self) -> &K { self.entry.key() }
+
+    /// Inserts `value` into the entry's node and returns a mutable reference
+    /// to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        for len in self.lens {
+            grow_len(len, 1);
+        }
+        self.entry.insert(value)
+    }
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    /// Gets the entry's key.
+    #[must_use]
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting `default` if it's vacant,
+    /// and returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Ensures a value is in the entry by inserting the result of `default` if
+    /// it's vacant, and returns a mutable reference to the value.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Calls `f` with the value if the entry is occupied.
+    #[must_use]
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Self::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<K, V, S, M> ManagedHashMapOctree<K, V, S, M>
+where
+    K: Eq + Hash,
+    (K, V): CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Gets the entry for `key` in the nodes along the path to the octant
+    /// containing `pos`, for in-place insertion or update.
+    ///
+    /// If the key isn't found, the entry is vacant and inserting into it adds
+    /// the value to the deepest existing node on that path, without
+    /// rebalancing, and counts it in the `len` of every node above. If `pos`
+    /// lies outside this node's bounds, only this node is searched and the
+    /// value is inserted here, as `add` would.
+    pub fn entry(&mut self, mut key: K, pos: (S, S, S)) -> Entry<'_, K, V> {
+        let inside = self.contains_point(pos);
+        let mut lens = Vec::new();
+        let mut node = self;
+        loop {
+            let idx = node.get_child_idx_for_point(pos);
+            let Self { children, data } = node;
+            let ManagedOctreeData { len, data, .. } = data;
+            let entry = match data.entry(key) {
+                hash_map::Entry::Occupied(entry) => {
+                    return Entry::Occupied(entry);
+                }
+                hash_map::Entry::Vacant(entry) => entry,
+            };
+            lens.push(len);
+            match children[idx].as_deref_mut().filter(|_| inside) {
+                Some(child) => {
+                    key = entry.into_key();
+                    node = child;
+                }
+                None => return Entry::Vacant(VacantEntry { entry, lens }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ManagedHashMapOctree;
+    use len_trait::Len;
+
+    #[test]
+    fn test_entry_or_insert_counts_once() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            );
+        let pos = (1.0, 2.0, 3.0);
+        assert_eq!(*o.entry(1, pos).or_insert(pos), pos);
+        assert_eq!(*o.entry(1, pos).or_insert((9.0, 9.0, 9.0)), pos);
+        assert_eq!(o.len(), 1);
+    }
+
+    #[test]
+    fn test_entry_in_child_updates_ancestors() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (-1.0, -1.0, -1.0)));
+        o.add((3, (600.0, 600.0, 600.0)));
+        o.rebalance();
+        assert!(o.get_child(7).and_then(|c| c.get_child(0)).is_some());
+
+        let pos = (2.0, 2.0, 2.0);
+        o.entry(4, pos).or_insert_with(|| pos);
+        let child = o.get_child(7).unwrap();
+        assert_eq!(o.len(), 4);
+        assert_eq!(child.len(), 3);
+        assert_eq!(child.get_child(0).unwrap().len(), 2);
+
+        let moved = o
+            .entry(1, (1.0, 1.0, 1.0))
+            .and_modify(|v| v.0 = 1.5)
+            .or_insert((0.0, 0.0, 0.0));
+        assert_eq!(*moved, (1.5, 1.0, 1.0));
+        assert_eq!(o.len(), 4);
+    }
+
+    #[test]
+    fn test_entry_outside_bounds_stays_in_node() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 2.0, 2.0)));
+        o.add((3, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        assert_eq!(o.get_child(7).unwrap().len(), 2);
+
+        let pos = (2000.0, 2000.0, 2000.0);
+        o.entry(4, pos).or_insert(pos);
+        assert_eq!(o.data.data.get(&4), Some(&pos));
+        assert_eq!(o.get_child(7).unwrap().len(), 2);
+        assert_eq!(o.len(), 4);
+    }
+}
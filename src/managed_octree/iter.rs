@@ -0,0 +1,474 @@
+use super::{CentredItem, ManagedOctree, OctreeCollectionRef};
+use alloc::vec::Vec;
+use core::{
+    mem,
+    ops::{Add, Div, Sub},
+};
+use len_trait::{Empty, Len};
+use num::One;
+
+/// A depth-first iterator over the items stored in a managed octree. Each
+/// node's own items are yielded before those of its children, which are
+/// visited in index order.
+struct Iter<'a, D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    stack: Vec<&'a ManagedOctree<D, S, M>>,
+    items: Option<D::Iter<'a>>,
+}
+
+impl<'a, D, S, M> Iterator for Iter<'a, D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    type Item = D::ItemRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.items.as_mut().and_then(Iterator::next) {
+                return Some(item);
+            }
+            let node = self.stack.pop()?;
+            self.stack.extend(
+                node.children.iter().rev().flatten().map(AsRef::as_ref),
+            );
+            self.items = Some(node.data.data.iter_items());
+        }
+    }
+}
+
+/// A depth-first iterator that consumes a managed octree and yields its items
+/// in the same order as `iter`.
+pub struct ManagedOctreeIntoIter<D, S, M = ()>
+where
+    D: Default + Empty + Len + IntoIterator,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    stack: Vec<ManagedOctree<D, S, M>>,
+    items: Option<D::IntoIter>,
+}
+
+impl<D, S, M> Iterator for ManagedOctreeIntoIter<D, S, M>
+where
+    D: Default + Empty + Len + IntoIterator,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    type Item = D::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.items.as_mut().and_then(Iterator::next) {
+                return Some(item);
+            }
+            let mut node = self.stack.pop()?;
+            self.stack.extend(
+                node.children
+                    .iter_mut()
+                    .rev()
+                    .filter_map(Option::take)
+                    .map(|c| *c),
+            );
+            self.items = Some(mem::take(&mut node.data.data).into_iter());
+        }
+    }
+}
+
+/// An item along with the centre and half lengths of the node holding it, as
+/// yielded by `iter_with_bounds`.
+type WithBounds<'a, D, S> = (
+    <D as OctreeCollectionRef<S>>::ItemRef<'a>,
+    (S, S, S),
+    (S, S, S),
+);
+
+impl<D, S, M> IntoIterator for ManagedOctree<D, S, M>
+where
+    D: Default + Empty + Len + IntoIterator,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    type IntoIter = ManagedOctreeIntoIter<D, S, M>;
+    type Item = D::Item;
+
+    /// Consumes the tree, yielding every item it holds in a deterministic
+    /// depth-first order.
+    fn into_iter(self) -> Self::IntoIter {
+        ManagedOctreeIntoIter {
+            stack: vec![self],
+            items: None,
+        }
+    }
+}
+
+impl<D, S, M> ManagedOctree<D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Iterates over every item in the tree in a deterministic depth-first
+    /// order, starting with the items stored at this node.
+    pub fn iter(&self) -> impl Iterator<Item = D::ItemRef<'_>> {
+        Iter {
+            stack: vec![self],
+            items: None,
+        }
+    }
+
+    /// Iterates over every item in the tree along with the centre and half
+    /// lengths of the node holding it, which is the resolution at which the
+    /// item is indexed. Nodes are visited breadth-first, as in `iter_nodes`.
+    pub fn iter_with_bounds(
+        &self,
+    ) -> impl Iterator<Item = WithBounds<'_, D, S>> {
+        self.iter_nodes().flat_map(|(node, _)| {
+            let (centre, half_lengths) =
+                (node.data.centre, node.data.half_lengths);
+            node.data
+                .data
+                .iter_items()
+                .map(move |item| (item, centre, half_lengths))
+        })
+    }
+
+    /// Collects every item in the subtree rooted at child `idx`, in the same
+    /// order as `iter`. Returns an empty vec if the child doesn't exist.
+    #[must_use]
+    pub fn items_in_child(&self, idx: usize) -> Vec<D::ItemRef<'_>> {
+        self.get_child(idx)
+            .map_or_else(Vec::new, |child| child.iter().collect())
+    }
+
+    /// Collects every item in the subtree rooted at the child on the given
+    /// side of each axis.
+    ///
+    /// ## Arguments
+    /// * `pos_x` - positive x axis if true, negative if false.
+    /// * `pos_y` - positive y axis if true, negative if false.
+    /// * `pos_z` - positive z axis if true, negative if false.
+    #[must_use]
+    pub fn items_in_child_at_pos(
+        &self,
+        pos_x: bool,
+        pos_y: bool,
+        pos_z: bool,
+    ) -> Vec<D::ItemRef<'_>> {
+        self.items_in_child(Self::get_child_idx_at_pos(pos_x, pos_y, pos_z))
+    }
+}
+
+/// Rotates the 3-bit value `bits` left by `by` places.
+const fn rotate_left_3(bits: usize, by: usize) -> usize {
+    let by = by % 3;
+    ((bits << by) | (bits >> (3 - by))) & 7
+}
+
+/// Gets the reflected binary Gray code of `w`.
+const fn gray_code(w: usize) -> usize { w ^ (w >> 1) }
+
+/// Gets the corner at which the `w`th cell of the base Hilbert curve is
+/// entered.
+const fn hilbert_entry(w: usize) -> usize {
+    if w == 0 {
+        0
+    } else {
+        gray_code(2 * ((w - 1) / 2))
+    }
+}
+
+/// Gets the axis along which the `w`th cell of the base Hilbert curve is
+/// exited.
+const fn hilbert_direction(w: usize) -> usize {
+    if w == 0 {
+        0
+    } else if w.is_multiple_of(2) {
+        (w - 1).trailing_ones() as usize % 3
+    } else {
+        w.trailing_ones() as usize % 3
+    }
+}
+
+impl<D, S, M> ManagedOctree<D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Collects every item in the tree ordered along a Hilbert curve through
+    /// the octants, so items close together in the result are close together
+    /// in space, which generally holds more often than for the Z-order of
+    /// `iter_nodes_morton`.
+    ///
+    /// Items held by an internal node are ordered as if they lay at the start
+    /// of the octant they belong to, and items within a node keep the order
+    /// in which they're stored.
+    #[must_use]
+    pub fn items_hilbert_order(&self) -> Vec<D::ItemRef<'_>> {
+        let mut items = Vec::with_capacity(self.data.len);
+        self.hilbert_order_into(0, 0, &mut items);
+        items
+    }
+
+    /// Visits the octants in the order of the Hilbert curve transformed to
+    /// enter at corner `entry` and exit along axis `direction` (following
+    /// Hamilton's "Compact Hilbert Indices").
+    fn hilbert_order_into<'a>(
+        &'a self,
+        entry: usize,
+        direction: usize,
+        items: &mut Vec<D::ItemRef<'a>>,
+    ) {
+        let mut octants: [Vec<D::ItemRef<'a>>; 8] = Default::default();
+        for item in self.data.data.iter_items() {
+            octants[self.get_child_idx_for_point(item.centre())].push(item);
+        }
+        for w in 0..8 {
+            let idx = rotate_left_3(gray_code(w), direction + 1) ^ entry;
+            items.append(&mut octants[idx]);
+            if let Some(child) = &self.children[idx] {
+                child.hilbert_order_into(
+                    entry ^ rotate_left_3(hilbert_entry(w), direction + 1),
+                    (direction + hilbert_direction(w) + 1) % 3,
+                    items,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use super::super::ManagedHashMapOctree;
+    use super::super::ManagedVecOctree;
+    use len_trait::Len;
+
+    #[test]
+    fn test_iter_vec_count() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, 1.0, -1.0));
+        o.rebalance();
+        assert!(o.get_child(7).is_some());
+        assert_eq!(o.iter().count(), o.len());
+        assert_eq!(
+            o.iter().collect::<Vec<_>>(),
+            vec![
+                &(-1.0, -1.0, -1.0),
+                &(-2.0, 1.0, -1.0),
+                &(1.0, 1.0, 1.0),
+                &(2.0, 2.0, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_items_in_child() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
+        o.rebalance();
+        assert_eq!(
+            o.items_in_child_at_pos(true, true, true),
+            vec![&(600.0, 600.0, 600.0), &(1.0, 1.0, 1.0)]
+        );
+        assert_eq!(o.items_in_child(0).len(), 2);
+        assert!(o.items_in_child(3).is_empty());
+        assert!(o.items_in_child(8).is_empty());
+    }
+
+    #[test]
+    fn test_into_iter_vec_count() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, 1.0, -1.0));
+        o.rebalance();
+        let len = o.len();
+        let expected: Vec<_> = o.iter().copied().collect();
+        let points: Vec<_> = o.into_iter().collect();
+        assert_eq!(points.len(), len);
+        assert_eq!(points, expected);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_into_iter_hash_count() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 2.0, 2.0)));
+        o.add((3, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        let len = o.len();
+        let mut entries: Vec<(u32, (f32, f32, f32))> = o.into_iter().collect();
+        entries.sort_by_key(|(key, _)| *key);
+        assert_eq!(entries.len(), len);
+        assert_eq!(entries[1], (2, (2.0, 2.0, 2.0)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_iter_hash_count() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            )
+            .with_max_size(1);
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (2.0, 2.0, 2.0)));
+        o.add((3, (-1.0, -1.0, -1.0)));
+        o.rebalance();
+        assert_eq!(o.iter().count(), o.len());
+    }
+
+    fn grid_tree(cells: i32) -> ManagedVecOctree<(i32, i32, i32), i32> {
+        let width = 256 / cells;
+        let mut o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed(
+            (0, 0, 0),
+            128,
+        )
+        .with_max_size(1);
+        let centres = (0..cells).map(|i| width * i - 128 + width / 2);
+        for x in centres.clone() {
+            for y in centres.clone() {
+                for z in centres.clone() {
+                    o.add((x, y, z));
+                }
+            }
+        }
+        o.rebalance();
+        o
+    }
+
+    #[test]
+    fn test_items_hilbert_order_steps_to_neighbours() {
+        for &cells in &[2, 4, 8] {
+            let o = grid_tree(cells);
+            let width = 256 / cells;
+            let items = o.items_hilbert_order();
+            assert_eq!(items.len(), o.len());
+            for pair in items.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let step =
+                    (a.0 - b.0).abs() + (a.1 - b.1).abs() + (a.2 - b.2).abs();
+                assert_eq!(step, width, "{a:?} -> {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_items_hilbert_order_internal_items() {
+        let mut o = grid_tree(4);
+        let expected: Vec<_> =
+            o.items_hilbert_order().into_iter().copied().collect();
+        o.add((-40, -40, -40));
+        let items = o.items_hilbert_order();
+        assert_eq!(items.len(), expected.len() + 1);
+        let pos = items.iter().position(|&&item| item == (-40, -40, -40));
+        let first_in_octant = expected
+            .iter()
+            .position(|item| item.0 < 0 && item.1 < 0 && item.2 < 0);
+        assert_eq!(pos, first_in_octant);
+    }
+
+    #[test]
+    fn test_iter_with_bounds() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 2.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+
+        let items: Vec<_> = o.iter_with_bounds().collect();
+        assert_eq!(items.len(), o.len());
+        let half_length_of = |point| {
+            items
+                .iter()
+                .find(|(item, ..)| **item == point)
+                .map(|(_, _, half_lengths)| half_lengths.0)
+                .unwrap()
+        };
+        let root_item = o.data.data[0];
+        assert_relative_eq!(half_length_of(root_item), 1000.0);
+        assert!(half_length_of((2.0, 2.0, 2.0)) < 1000.0);
+        assert!(half_length_of((1.0, 1.0, 1.0)) < 1000.0);
+        for (item, centre, half_lengths) in items {
+            assert!(o.iter_nodes().any(|(node, _)| {
+                node.centre() == centre
+                    && node.half_lengths() == half_lengths
+                    && node.data.data.contains(item)
+            }));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::{CentredItem, ManagedOctree, OctreeCollectionRef};
+use core::ops::{Add, Div, Mul, Sub};
+use len_trait::{Empty, Len};
+use num::One;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A trait for items with a weight (e.g. mass), used to compute the mass
+/// distribution of a tree.
+pub trait Weighted<S> {
+    fn weight(&self) -> S;
+}
+
+impl<S, T> Weighted<S> for &T
+where
+    T: Weighted<S> + ?Sized,
+{
+    fn weight(&self) -> S { (**self).weight() }
+}
+
+/// The total weight of the items in a subtree along with their centre of
+/// mass, stored as node metadata by `compute_mass_distribution`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MassDistribution<S> {
+    pub centre_of_mass: (S, S, S),
+    pub weight: S,
+}
+
+impl<D, S> ManagedOctree<D, S, MassDistribution<S>>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    for<'a> D::ItemRef<'a>: Weighted<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>
+        + Div<S, Output = S>,
+{
+    /// Sets the metadata of every node bottom-up to the total weight and
+    /// centre of mass of the items in its subtree. A subtree with a total
+    /// weight of zero has its node's centre as its centre of mass.
+    ///
+    /// As with `recompute_meta`, this must be called again after changing the
+    /// tree.
+    pub fn compute_mass_distribution(&mut self) {
+        let zero = S::default();
+        let mut weight = zero;
+        let mut moment = (zero, zero, zero);
+        let mut accumulate = |(x, y, z): (S, S, S), w: S| {
+            weight = weight + w;
+            moment = (moment.0 + x * w, moment.1 + y * w, moment.2 + z * w);
+        };
+        for item in self.data.data.iter_items() {
+            accumulate(item.centre(), item.weight());
+        }
+        for child in self.children.iter_mut().flatten() {
+            child.compute_mass_distribution();
+            let mass = child.data.meta;
+            accumulate(mass.centre_of_mass, mass.weight);
+        }
+        let centre_of_mass = if weight == zero {
+            self.data.centre
+        } else {
+            (moment.0 / weight, moment.1 / weight, moment.2 / weight)
+        };
+        self.data.meta = MassDistribution {
+            centre_of_mass,
+            weight,
+        };
+    }
+
+    /// Walks the tree as in the Barnes-Hut algorithm, calling `f` with the
+    /// centre of mass and total weight of each group of items acting on
+    /// `point`. A node whose largest half length is less than `theta` times
+    /// its centre of mass's distance from `point` is passed to `f` as a whole,
+    /// otherwise its own items are passed individually and its children are
+    /// visited in turn. A `theta` of zero therefore visits every item.
+    ///
+    /// This relies on the metadata set by `compute_mass_distribution`, and
+    /// nodes with a total weight of zero are skipped.
+    pub fn approx_field<F>(&self, point: (S, S, S), theta: S, mut f: F)
+    where
+        F: FnMut((S, S, S), S),
+    {
+        self.approx_field_with(point, theta * theta, &mut f);
+    }
+
+    fn approx_field_with<F>(&self, point: (S, S, S), theta_sq: S, f: &mut F)
+    where
+        F: FnMut((S, S, S), S),
+    {
+        let mass = self.data.meta;
+        if mass.weight == S::default() {
+            return;
+        }
+        let (hx, hy, hz) = self.data.half_lengths;
+        let h = if hx > hy { hx } else { hy };
+        let h = if hz > h { hz } else { h };
+        let (dx, dy, dz) = (
+            mass.centre_of_mass.0 - point.0,
+            mass.centre_of_mass.1 - point.1,
+            mass.centre_of_mass.2 - point.2,
+        );
+        if h * h < theta_sq * (dx * dx + dy * dy + dz * dz) {
+            f(mass.centre_of_mass, mass.weight);
+            return;
+        }
+        for item in self.data.data.iter_items() {
+            f(item.centre(), item.weight());
+        }
+        for child in self.children.iter().flatten() {
+            child.approx_field_with(point, theta_sq, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{CentredItem, ManagedVecOctree},
+        MassDistribution,
+        Weighted,
+    };
+
+    struct Body {
+        pos: (f32, f32, f32),
+        mass: f32,
+    }
+
+    impl CentredItem<f32> for Body {
+        fn centre(&self) -> (f32, f32, f32) { self.pos }
+    }
+
+    impl Weighted<f32> for Body {
+        fn weight(&self) -> f32 { self.mass }
+    }
+
+    type BodyOctree = ManagedVecOctree<Body, f32, MassDistribution<f32>>;
+
+    #[test]
+    fn test_mass_distribution_midpoint() {
+        let mut o =
+            BodyOctree::new_managed((0.0, 0.0, 0.0), 1000.0).with_max_size(1);
+        o.add(Body {
+            pos: (-10.0, -10.0, -10.0),
+            mass: 2.0,
+        });
+        o.add(Body {
+            pos: (30.0, 30.0, 30.0),
+            mass: 2.0,
+        });
+        o.rebalance();
+        o.compute_mass_distribution();
+
+        let root = o.meta();
+        assert_relative_eq!(root.weight, 4.0);
+        assert_relative_eq!(root.centre_of_mass.0, 10.0);
+        assert_relative_eq!(root.centre_of_mass.1, 10.0);
+        assert_relative_eq!(root.centre_of_mass.2, 10.0);
+        let child = o.get_child(0).unwrap().meta();
+        assert_relative_eq!(child.weight, 2.0);
+        assert_relative_eq!(child.centre_of_mass.0, -10.0);
+    }
+
+    #[test]
+    fn test_mass_distribution_weighted_and_empty() {
+        let mut o = BodyOctree::new_managed((5.0, 5.0, 5.0), 100.0);
+        o.compute_mass_distribution();
+        assert_eq!(
+            *o.meta(),
+            MassDistribution {
+                centre_of_mass: (5.0, 5.0, 5.0),
+                weight: 0.0,
+            }
+        );
+
+        o.add(Body {
+            pos: (0.0, 0.0, 0.0),
+            mass: 3.0,
+        });
+        o.add(Body {
+            pos: (4.0, 8.0, 0.0),
+            mass: 1.0,
+        });
+        o.compute_mass_distribution();
+        assert_relative_eq!(o.meta().centre_of_mass.0, 1.0);
+        assert_relative_eq!(o.meta().centre_of_mass.1, 2.0);
+        assert_relative_eq!(o.meta().weight, 4.0);
+    }
+
+    fn potential(
+        sources: impl IntoIterator<Item = ((f32, f32, f32), f32)>,
+        point: (f32, f32, f32),
+    ) -> f32 {
+        sources
+            .into_iter()
+            .map(|((x, y, z), w)| {
+                let (dx, dy, dz) = (x - point.0, y - point.1, z - point.2);
+                w / dx.hypot(dy).hypot(dz)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_approx_field_close_to_exact() {
+        let mut o =
+            BodyOctree::new_managed((0.0, 0.0, 0.0), 1000.0).with_max_size(4);
+        let mut bodies = Vec::new();
+        for i in 0..500_u16 {
+            let f = f32::from(i);
+            let pos = (
+                (f * 37.1) % 900.0 - 450.0,
+                (f * 13.7) % 800.0 - 400.0,
+                (f * 7.3) % 700.0 - 350.0,
+            );
+            let mass = 1.0 + (f * 3.3) % 5.0;
+            bodies.push((pos, mass));
+            o.add(Body { pos, mass });
+        }
+        o.rebalance();
+        o.compute_mass_distribution();
+
+        let point = (2000.0, -1500.0, 1200.0);
+        let exact = potential(bodies.iter().copied(), point);
+
+        let mut all = Vec::new();
+        o.approx_field(point, 0.0, |com, w| all.push((com, w)));
+        assert_eq!(all.len(), bodies.len());
+        assert_relative_eq!(potential(all, point), exact, max_relative = 1e-5);
+
+        let mut approx = Vec::new();
+        o.approx_field(point, 0.3, |com, w| approx.push((com, w)));
+        assert!(approx.len() < bodies.len());
+        assert_relative_eq!(
+            potential(approx, point),
+            exact,
+            max_relative = 1e-3
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use super::query::{box_distance_sq, distance_sq};
+use core::ops::{Add, Mul, Sub};
+
+/// A way of measuring the distance between points, used to parameterise
+/// `nearest_by` and `query_radius_by`.
+///
+/// The values returned needn't be true distances, only increase with them, so
+/// long as `from_radius` converts a radius to the same scale. This lets
+/// `Euclidean` compare squared distances without needing a square root.
+pub trait Metric<S> {
+    /// Gets the distance between two points.
+    fn distance(a: (S, S, S), b: (S, S, S)) -> S;
+
+    /// Gets the distance from `point` to the closest point of the box with the
+    /// given centre and half lengths, which is zero if the point lies within
+    /// the box. This is a lower bound on the distance to anything inside it.
+    fn node_min_distance(
+        point: (S, S, S),
+        centre: (S, S, S),
+        half_lengths: (S, S, S),
+    ) -> S;
+
+    /// Converts a radius to the scale of values returned by `distance`.
+    fn from_radius(radius: S) -> S { radius }
+}
+
+/// Straight-line distance. Distances are squared, as are radii.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Euclidean;
+
+/// The sum of the distances along each axis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Manhattan;
+
+/// The largest of the distances along each axis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Chebyshev;
+
+/// Gets the distance between two values on an axis.
+fn axis_distance<S>(a: S, b: S) -> S
+where
+    S: PartialOrd + Sub<S, Output = S>,
+{
+    if a > b { a - b } else { b - a }
+}
+
+/// Gets the distance from a value to the span `centre ± half_length`, which is
+/// zero within it.
+fn axis_gap<S>(p: S, centre: S, half_length: S) -> S
+where
+    S: Default + Copy + PartialOrd + Sub<S, Output = S>,
+{
+    let d = axis_distance(p, centre);
+    if d > half_length {
+        d - half_length
+    } else {
+        S::default()
+    }
+}
+
+impl<S> Metric<S> for Euclidean
+where
+    S: Copy
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>,
+{
+    fn distance(a: (S, S, S), b: (S, S, S)) -> S { distance_sq(a, b) }
+
+    fn node_min_distance(
+        point: (S, S, S),
+        centre: (S, S, S),
+        half_lengths: (S, S, S),
+    ) -> S {
+        box_distance_sq(point, centre, half_lengths)
+    }
+
+    fn from_radius(radius: S) -> S { radius * radius }
+}
+
+impl<S> Metric<S> for Manhattan
+where
+    S: Default + Copy + PartialOrd + Add<S, Output = S> + Sub<S, Output = S>,
+{
+    fn distance(a: (S, S, S), b: (S, S, S)) -> S {
+        axis_distance(a.0, b.0)
+            + axis_distance(a.1, b.1)
+            + axis_distance(a.2, b.2)
+    }
+
+    fn node_min_distance(
+        point: (S, S, S),
+        centre: (S, S, S),
+        half_lengths: (S, S, S),
+    ) -> S {
+        axis_gap(point.0, centre.0, half_lengths.0)
+            + axis_gap(point.1, centre.1, half_lengths.1)
+            + axis_gap(point.2, centre.2, half_lengths.2)
+    }
+}
+
+/// Gets the largest of three values.
+fn max3<S>(a: S, b: S, c: S) -> S
+where
+    S: PartialOrd,
+{
+    let ab = if a > b { a } else { b };
+    if c > ab { c } else { ab }
+}
+
+impl<S> Metric<S> for Chebyshev
+where
+    S: Default + Copy + PartialOrd + Sub<S, Output = S>,
+{
+    fn distance(a: (S, S, S), b: (S, S, S)) -> S {
+        max3(
+            axis_distance(a.0, b.0),
+            axis_distance(a.1, b.1),
+            axis_distance(a.2, b.2),
+        )
+    }
+
+    fn node_min_distance(
+        point: (S, S, S),
+        centre: (S, S, S),
+        half_lengths: (S, S, S),
+    ) -> S {
+        max3(
+            axis_gap(point.0, centre.0, half_lengths.0),
+            axis_gap(point.1, centre.1, half_lengths.1),
+            axis_gap(point.2, centre.2, half_lengths.2),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::ManagedVecOctree, Chebyshev, Euclidean, Manhattan};
+
+    fn tree() -> ManagedVecOctree<(i32, i32, i32), i32> {
+        let mut o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed(
+            (0, 0, 0),
+            100,
+        )
+        .with_max_size(1);
+        o.add((4, 4, 4));
+        o.add((6, 0, 0));
+        o.add((-50, 60, -70));
+        o.rebalance();
+        o
+    }
+
+    #[test]
+    fn test_nearest_by_metric() {
+        let o = tree();
+        // Euclidean: 48 vs 36, Manhattan: 12 vs 6, Chebyshev: 4 vs 6.
+        assert_eq!(o.nearest((0, 0, 0)), Some(&(6, 0, 0)));
+        assert_eq!(o.nearest_by::<Euclidean>((0, 0, 0)), Some(&(6, 0, 0)));
+        assert_eq!(o.nearest_by::<Manhattan>((0, 0, 0)), Some(&(6, 0, 0)));
+        assert_eq!(o.nearest_by::<Chebyshev>((0, 0, 0)), Some(&(4, 4, 4)));
+    }
+
+    #[test]
+    fn test_query_radius_by_metric() {
+        let o = tree();
+        let mut euclidean = o.query_radius_by::<Euclidean>((0, 0, 0), 6);
+        euclidean.sort_unstable();
+        assert_eq!(euclidean, vec![&(6, 0, 0)]);
+        assert_eq!(euclidean, o.query_radius((0, 0, 0), 6));
+
+        let mut chebyshev = o.query_radius_by::<Chebyshev>((0, 0, 0), 6);
+        chebyshev.sort_unstable();
+        assert_eq!(chebyshev, vec![&(4, 4, 4), &(6, 0, 0)]);
+
+        assert_eq!(o.query_radius_by::<Manhattan>((0, 0, 0), 11).len(), 1);
+        assert_eq!(o.query_radius_by::<Manhattan>((0, 0, 0), 12).len(), 2);
+    }
+}
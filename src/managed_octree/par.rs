@@ -0,0 +1,71 @@
+use super::{CentredItem, ManagedOctree, OctreeCollection};
+use core::ops::{Add, Div, Sub};
+use len_trait::{Clear, Empty, Len};
+use num::One;
+use rayon::prelude::*;
+
+impl<D, S, T, M> ManagedOctree<D, S, M>
+where
+    D: Default
+        + Empty
+        + Len
+        + Clear
+        + IntoIterator<Item = T>
+        + OctreeCollection<T>
+        + Send,
+    T: CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>
+        + Send,
+    M: Default + Send,
+{
+    /// Equivalent to `rebalance`, but once this node's items have been
+    /// distributed between its children, the children are rebalanced in
+    /// parallel.
+    pub fn par_rebalance(&mut self) {
+        self.rebalance_node();
+        self.children
+            .par_iter_mut()
+            .flatten()
+            .filter(|child| child.over_split_threshold())
+            .for_each(|child| child.par_rebalance());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ManagedVecOctree;
+    use len_trait::Len;
+
+    #[test]
+    fn test_par_rebalance_matches_rebalance() {
+        let mut seq = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4);
+        for i in 0..2000_u16 {
+            let f = f32::from(i);
+            seq.add(((f * 7.3) % 1000.0, (f * 3.1) % 900.0, -(f % 500.0)));
+        }
+        let mut par = seq.clone();
+        seq.rebalance();
+        par.par_rebalance();
+
+        assert_eq!(par.len(), seq.len());
+        assert_eq!(par.node_count(), seq.node_count());
+        for ((a, a_depth), (b, b_depth)) in
+            seq.iter_nodes().zip(par.iter_nodes())
+        {
+            assert_eq!(a_depth, b_depth);
+            assert_eq!(a.data.centre, b.data.centre);
+            assert_eq!(a.data.len, b.data.len);
+            assert_eq!(a.data.data, b.data.data);
+        }
+    }
+}
@@ -0,0 +1,1627 @@
+use super::{
+    Bounds,
+    CentredItem,
+    Euclidean,
+    ManagedOctree,
+    ManagedVecOctree,
+    Metric,
+    OctreeCollectionRef,
+    point_bounds,
+    shrink_len,
+};
+use alloc::{collections::BinaryHeap, vec::Vec};
+use core::{
+    cmp::Ordering,
+    mem,
+    ops::{Add, Div, Mul, Sub},
+};
+use len_trait::{Empty, Len};
+#[cfg(feature = "std")]
+use num::Float;
+use num::One;
+
+/// Gets the squared Euclidean distance between two points.
+pub(super) fn distance_sq<S>(a: (S, S, S), b: (S, S, S)) -> S
+where
+    S: Copy + Add<S, Output = S> + Sub<S, Output = S> + Mul<S, Output = S>,
+{
+    let (dx, dy, dz) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+    dx * dx + dy * dy + dz * dz
+}
+
+/// Gets the squared Euclidean distance from a point to the closest point of a
+/// node's box, which is zero if the point lies within the box.
+pub(super) fn box_distance_sq<S>(
+    point: (S, S, S),
+    centre: (S, S, S),
+    half_lengths: (S, S, S),
+) -> S
+where
+    S: Copy
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>,
+{
+    let clamp = |p: S, c: S, h: S| {
+        if p < c - h {
+            c - h
+        } else if p > c + h {
+            c + h
+        } else {
+            p
+        }
+    };
+    let closest = (
+        clamp(point.0, centre.0, half_lengths.0),
+        clamp(point.1, centre.1, half_lengths.1),
+        clamp(point.2, centre.2, half_lengths.2),
+    );
+    distance_sq(point, closest)
+}
+
+/// Gets the squared Euclidean distance between the closest points of two
+/// nodes' boxes, which is zero if they touch or overlap.
+fn boxes_distance_sq<S>(
+    (a_centre, a_half): ((S, S, S), (S, S, S)),
+    (b_centre, b_half): ((S, S, S), (S, S, S)),
+) -> S
+where
+    S: Default
+        + Copy
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>,
+{
+    let gap = |a: S, b: S, h: S| {
+        let d = if a > b { a - b } else { b - a };
+        if d > h { d - h } else { S::default() }
+    };
+    let (gx, gy, gz) = (
+        gap(a_centre.0, b_centre.0, a_half.0 + b_half.0),
+        gap(a_centre.1, b_centre.1, a_half.1 + b_half.1),
+        gap(a_centre.2, b_centre.2, a_half.2 + b_half.2),
+    );
+    gx * gx + gy * gy + gz * gz
+}
+
+/// One of the three coordinate axes, used to pick a component of a point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Gets the component of `point` along this axis.
+    pub fn of<S>(self, (x, y, z): (S, S, S)) -> S {
+        match self {
+            Self::X => x,
+            Self::Y => y,
+            Self::Z => z,
+        }
+    }
+}
+
+/// Checks whether a point lies within an axis-aligned box, inclusive of its
+/// faces.
+fn aabb_contains<S>(point: (S, S, S), min: (S, S, S), max: (S, S, S)) -> bool
+where
+    S: Copy + PartialOrd,
+{
+    min.0 <= point.0
+        && point.0 <= max.0
+        && min.1 <= point.1
+        && point.1 <= max.1
+        && min.2 <= point.2
+        && point.2 <= max.2
+}
+
+/// Checks whether a node's box overlaps an axis-aligned box.
+fn box_overlaps_aabb<S>(
+    centre: (S, S, S),
+    half_lengths: (S, S, S),
+    min: (S, S, S),
+    max: (S, S, S),
+) -> bool
+where
+    S: Copy + PartialOrd + Add<S, Output = S> + Sub<S, Output = S>,
+{
+    centre.0 - half_lengths.0 <= max.0
+        && centre.0 + half_lengths.0 >= min.0
+        && centre.1 - half_lengths.1 <= max.1
+        && centre.1 + half_lengths.1 >= min.1
+        && centre.2 - half_lengths.2 <= max.2
+        && centre.2 + half_lengths.2 >= min.2
+}
+
+/// Gets the signed distance-like value `ax + by + cz + d` of a point relative
+/// to a plane `(a, b, c, d)`, which is non-negative on the inside.
+fn plane_side<S>((a, b, c, d): (S, S, S, S), (px, py, pz): (S, S, S)) -> S
+where
+    S: Copy + Add<S, Output = S> + Mul<S, Output = S>,
+{
+    a * px + b * py + c * pz + d
+}
+
+/// Checks whether a node's box lies entirely on the outside of a plane, by
+/// testing the corner furthest along the plane's normal.
+fn box_outside_plane<S>(
+    plane: (S, S, S, S),
+    centre: (S, S, S),
+    half_lengths: (S, S, S),
+) -> bool
+where
+    S: Default
+        + Copy
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>,
+{
+    let zero = S::default();
+    let furthest = |n: S, c: S, h: S| if n >= zero { c + h } else { c - h };
+    let corner = (
+        furthest(plane.0, centre.0, half_lengths.0),
+        furthest(plane.1, centre.1, half_lengths.1),
+        furthest(plane.2, centre.2, half_lengths.2),
+    );
+    plane_side(plane, corner) < zero
+}
+
+/// Checks whether the points `origin + t * dir` for `t` from zero up to
+/// `t_max`, or without limit if it's `None`, intersect a node's box using the
+/// slab method, without dividing by any zero components of `dir`.
+fn line_hits_box<S>(
+    origin: (S, S, S),
+    dir: (S, S, S),
+    t_max: Option<S>,
+    centre: (S, S, S),
+    half_lengths: (S, S, S),
+) -> bool
+where
+    S: Default
+        + Copy
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+{
+    let zero = S::default();
+    let mut t_enter = zero;
+    let mut t_exit = t_max;
+    for &(o, d, c, h) in &[
+        (origin.0, dir.0, centre.0, half_lengths.0),
+        (origin.1, dir.1, centre.1, half_lengths.1),
+        (origin.2, dir.2, centre.2, half_lengths.2),
+    ] {
+        let (min, max) = (c - h, c + h);
+        if d == zero {
+            if o < min || o > max {
+                return false;
+            }
+            continue;
+        }
+        let (t1, t2) = ((min - o) / d, (max - o) / d);
+        let (t1, t2) = if t1 > t2 { (t2, t1) } else { (t1, t2) };
+        if t1 > t_enter {
+            t_enter = t1;
+        }
+        if t_exit.is_none_or(|t_exit| t2 < t_exit) {
+            t_exit = Some(t2);
+        }
+    }
+    t_exit.is_none_or(|t_exit| t_exit >= t_enter)
+}
+
+/// A lazy depth-first iterator over the items whose centre lies within a
+/// sphere, skipping children whose box does not intersect it. Items are
+/// yielded in the same order as `query_radius`.
+struct RadiusIter<'a, D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    centre: (S, S, S),
+    radius_sq: S,
+    stack: Vec<&'a ManagedOctree<D, S, M>>,
+    items: Option<D::Iter<'a>>,
+}
+
+impl<'a, D, S, M> Iterator for RadiusIter<'a, D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    type Item = D::ItemRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(items) = &mut self.items {
+                for item in items {
+                    if distance_sq(item.centre(), self.centre) <= self.radius_sq
+                    {
+                        return Some(item);
+                    }
+                }
+            }
+            let node = self.stack.pop()?;
+            let (centre, radius_sq) = (self.centre, self.radius_sq);
+            self.stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .flatten()
+                    .filter(|child| {
+                        box_distance_sq(
+                            centre,
+                            child.data.centre,
+                            child.data.half_lengths,
+                        ) <= radius_sq
+                    })
+                    .map(AsRef::as_ref),
+            );
+            self.items = Some(node.data.data.iter_items());
+        }
+    }
+}
+
+/// An item found during a k-nearest search, ordered by its squared distance
+/// from the query point so that the furthest item sits at the top of a
+/// `BinaryHeap`.
+struct Neighbour<S, I> {
+    dist_sq: S,
+    item: I,
+}
+
+impl<S, I> PartialEq for Neighbour<S, I>
+where
+    S: PartialOrd,
+{
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl<S, I> Eq for Neighbour<S, I> where S: PartialOrd {}
+
+impl<S, I> PartialOrd for Neighbour<S, I>
+where
+    S: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S, I> Ord for Neighbour<S, I>
+where
+    S: PartialOrd,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist_sq
+            .partial_cmp(&other.dist_sq)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<D, S, M> ManagedOctree<D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Gets all items whose centre lies within `radius` of `centre`.
+    ///
+    /// Children whose box does not intersect the query sphere are skipped.
+    /// Items buffered at this node are always checked, even if they lie
+    /// outside of its box.
+    #[must_use]
+    pub fn query_radius(
+        &self,
+        centre: (S, S, S),
+        radius: S,
+    ) -> Vec<D::ItemRef<'_>> {
+        self.query_radius_by::<Euclidean>(centre, radius)
+    }
+
+    /// Gets all items whose centre lies within `radius` of `centre` as measured
+    /// by the metric `Dist`, pruning children as in `query_radius`.
+    #[must_use]
+    pub fn query_radius_by<Dist>(
+        &self,
+        centre: (S, S, S),
+        radius: S,
+    ) -> Vec<D::ItemRef<'_>>
+    where
+        Dist: Metric<S>,
+    {
+        let mut result = Vec::new();
+        self.visit_radius::<Dist, _>(
+            centre,
+            Dist::from_radius(radius),
+            &mut |item| result.push(item),
+        );
+        result
+    }
+
+    /// Lazily iterates over the items whose centre lies within `radius` of
+    /// `centre`, in the same order as `query_radius`. Nodes are only visited
+    /// as the iterator is advanced, so stopping early, e.g. to check whether
+    /// any item lies within the sphere, skips the rest of the traversal.
+    pub fn query_radius_iter(
+        &self,
+        centre: (S, S, S),
+        radius: S,
+    ) -> impl Iterator<Item = D::ItemRef<'_>> {
+        RadiusIter {
+            centre,
+            radius_sq: radius * radius,
+            stack: vec![self],
+            items: None,
+        }
+    }
+
+    /// Counts the items whose centre lies within `radius` of `centre`, pruning
+    /// children in the same way as `query_radius` but without collecting them.
+    #[must_use]
+    pub fn count_in_radius(&self, centre: (S, S, S), radius: S) -> usize {
+        let mut count = 0;
+        self.visit_radius::<Euclidean, _>(centre, radius * radius, &mut |_| {
+            count += 1;
+        });
+        count
+    }
+
+    /// Gets all items whose centre lies within the axis-aligned box spanning
+    /// `min` to `max` (inclusive).
+    ///
+    /// Children whose bounds do not overlap the box are skipped. An empty vec
+    /// is returned if `min` is greater than `max` on any axis.
+    #[must_use]
+    pub fn query_aabb(
+        &self,
+        min: (S, S, S),
+        max: (S, S, S),
+    ) -> Vec<D::ItemRef<'_>> {
+        let mut result = Vec::new();
+        if min.0 <= max.0 && min.1 <= max.1 && min.2 <= max.2 {
+            self.query_aabb_into(min, max, &mut result);
+        }
+        result
+    }
+
+    fn query_aabb_into<'a>(
+        &'a self,
+        min: (S, S, S),
+        max: (S, S, S),
+        result: &mut Vec<D::ItemRef<'a>>,
+    ) {
+        result.extend(
+            self.data
+                .data
+                .iter_items()
+                .filter(|item| aabb_contains(item.centre(), min, max)),
+        );
+        for child in self.children.iter().flatten() {
+            if box_overlaps_aabb(
+                child.data.centre,
+                child.data.half_lengths,
+                min,
+                max,
+            ) {
+                child.query_aabb_into(min, max, result);
+            }
+        }
+    }
+
+    /// Gets all items whose coordinate on `axis` lies between `min` and `max`
+    /// (inclusive), whatever their other coordinates.
+    ///
+    /// Children whose bounds do not overlap that band on `axis` are skipped. An
+    /// empty vec is returned if `min` is greater than `max`.
+    #[must_use]
+    pub fn query_slab(
+        &self,
+        axis: Axis,
+        min: S,
+        max: S,
+    ) -> Vec<D::ItemRef<'_>> {
+        let mut result = Vec::new();
+        if min <= max {
+            self.query_slab_into(axis, min, max, &mut result);
+        }
+        result
+    }
+
+    fn query_slab_into<'a>(
+        &'a self,
+        axis: Axis,
+        min: S,
+        max: S,
+        result: &mut Vec<D::ItemRef<'a>>,
+    ) {
+        result.extend(self.data.data.iter_items().filter(|item| {
+            let p = axis.of(item.centre());
+            min <= p && p <= max
+        }));
+        for child in self.children.iter().flatten() {
+            let c = axis.of(child.data.centre);
+            let h = axis.of(child.data.half_lengths);
+            if c - h <= max && c + h >= min {
+                child.query_slab_into(axis, min, max, result);
+            }
+        }
+    }
+
+    /// Gets all items whose centre lies inside every one of `planes`, such as
+    /// those of a view frustum. Each plane `(a, b, c, d)` has its inside where
+    /// `ax + by + cz + d >= 0`.
+    ///
+    /// Children whose box lies entirely outside of any plane are skipped.
+    #[must_use]
+    pub fn query_frustum(
+        &self,
+        planes: [(S, S, S, S); 6],
+    ) -> Vec<D::ItemRef<'_>> {
+        let mut result = Vec::new();
+        self.query_frustum_into(&planes, &mut result);
+        result
+    }
+
+    fn query_frustum_into<'a>(
+        &'a self,
+        planes: &[(S, S, S, S); 6],
+        result: &mut Vec<D::ItemRef<'a>>,
+    ) {
+        let zero = S::default();
+        result.extend(self.data.data.iter_items().filter(|item| {
+            planes
+                .iter()
+                .all(|&plane| plane_side(plane, item.centre()) >= zero)
+        }));
+        for child in self.children.iter().flatten() {
+            if !planes.iter().any(|&plane| {
+                box_outside_plane(
+                    plane,
+                    child.data.centre,
+                    child.data.half_lengths,
+                )
+            }) {
+                child.query_frustum_into(planes, result);
+            }
+        }
+    }
+
+    /// Gets the item whose centre is closest to `point`, or `None` if the tree
+    /// is empty.
+    ///
+    /// The octant containing `point` is searched first, and any child whose
+    /// box is further away than the closest item found so far is skipped.
+    #[must_use]
+    pub fn nearest(&self, point: (S, S, S)) -> Option<D::ItemRef<'_>> {
+        self.nearest_by::<Euclidean>(point)
+    }
+
+    /// Gets the item whose centre is closest to `point` as measured by the
+    /// metric `Dist`, pruning children as in `nearest`.
+    #[must_use]
+    pub fn nearest_by<Dist>(&self, point: (S, S, S)) -> Option<D::ItemRef<'_>>
+    where
+        Dist: Metric<S>,
+    {
+        let mut best = None;
+        self.nearest_into::<Dist>(point, &mut best);
+        best.map(|(item, _)| item)
+    }
+
+    fn nearest_into<'a, Dist>(
+        &'a self,
+        point: (S, S, S),
+        best: &mut Option<(D::ItemRef<'a>, S)>,
+    ) where
+        Dist: Metric<S>,
+    {
+        for item in self.data.data.iter_items() {
+            let dist = Dist::distance(item.centre(), point);
+            if best.as_ref().is_none_or(|(_, best_dist)| dist < *best_dist) {
+                *best = Some((item, dist));
+            }
+        }
+        for (dist, child) in self.children_by_distance::<Dist>(point) {
+            if best.as_ref().is_none_or(|(_, best_dist)| dist < *best_dist) {
+                child.nearest_into::<Dist>(point, best);
+            }
+        }
+    }
+
+    /// Gets the item in this node's own buffer whose centre is closest to the
+    /// node's centre, ignoring its children, as a cheap stand-in for the whole
+    /// region. Returns `None` if the buffer is empty.
+    #[must_use]
+    pub fn representative(&self) -> Option<D::ItemRef<'_>> {
+        let centre = self.data.centre;
+        self.data.data.iter_items().min_by(|a, b| {
+            distance_sq(a.centre(), centre)
+                .partial_cmp(&distance_sq(b.centre(), centre))
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+
+    /// Gets up to `k` items closest to `point`, sorted by ascending distance.
+    ///
+    /// Once `k` items have been found, any child whose box is further away
+    /// than the furthest of them is skipped. If the tree holds fewer than `k`
+    /// items, all of them are returned.
+    #[must_use]
+    pub fn k_nearest(&self, point: (S, S, S), k: usize) -> Vec<D::ItemRef<'_>> {
+        let mut heap = BinaryHeap::with_capacity(k);
+        if k > 0 {
+            self.k_nearest_into(point, k, &mut heap);
+        }
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|neighbour| neighbour.item)
+            .collect()
+    }
+
+    fn k_nearest_into<'a>(
+        &'a self,
+        point: (S, S, S),
+        k: usize,
+        heap: &mut BinaryHeap<Neighbour<S, D::ItemRef<'a>>>,
+    ) {
+        for item in self.data.data.iter_items() {
+            let dist_sq = distance_sq(item.centre(), point);
+            if heap.len() < k {
+                heap.push(Neighbour { dist_sq, item });
+            } else if heap.peek().is_some_and(|n| dist_sq < n.dist_sq) {
+                heap.pop();
+                heap.push(Neighbour { dist_sq, item });
+            }
+        }
+        for (dist_sq, child) in self.children_by_distance::<Euclidean>(point) {
+            if heap.len() < k
+                || heap.peek().is_some_and(|n| dist_sq < n.dist_sq)
+            {
+                child.k_nearest_into(point, k, heap);
+            }
+        }
+    }
+
+    /// Gets the minimum and maximum corners of the smallest box containing the
+    /// centre of every item in the tree, or `None` if the tree is empty. This
+    /// is often much smaller than the root's bounds.
+    #[must_use]
+    pub fn content_bounds(&self) -> Option<Bounds<S>> {
+        point_bounds(self.iter().map(|item| item.centre()))
+    }
+
+    /// Gets the smallest squared distance between the centres of any two items
+    /// held by the node that `point` belongs to (see `locate`), ignoring its
+    /// children. A result of zero means that node holds coincident items,
+    /// which no amount of splitting can separate, so only `max_depth` stops
+    /// `rebalance` from subdividing it further.
+    ///
+    /// Returns `None` if `point` lies outside of this node or the located node
+    /// holds fewer than two items.
+    #[must_use]
+    pub fn min_pairwise_distance_sq_in_leaf(
+        &self,
+        point: (S, S, S),
+    ) -> Option<S> {
+        let centres: Vec<(S, S, S)> = self
+            .locate(point)?
+            .data
+            .data
+            .iter_items()
+            .map(|item| item.centre())
+            .collect();
+        let mut min: Option<S> = None;
+        for (i, &a) in centres.iter().enumerate() {
+            for &b in &centres[i + 1..] {
+                let dist_sq = distance_sq(a, b);
+                if min.is_none_or(|min| dist_sq < min) {
+                    min = Some(dist_sq);
+                }
+            }
+        }
+        min
+    }
+
+    /// Gets the smallest distance between the centres of any two items held by
+    /// the node that `point` belongs to. This is the square root of
+    /// `min_pairwise_distance_sq_in_leaf`, which works for any `S`.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn min_pairwise_distance_in_leaf(&self, point: (S, S, S)) -> Option<S>
+    where
+        S: Float,
+    {
+        self.min_pairwise_distance_sq_in_leaf(point)
+            .map(Float::sqrt)
+    }
+
+    /// Gets the present children along with the distance from `point` to their
+    /// box as measured by `Dist`, closest first.
+    fn children_by_distance<Dist>(&self, point: (S, S, S)) -> Vec<(S, &Self)>
+    where
+        Dist: Metric<S>,
+    {
+        let mut children: Vec<(S, &Self)> = self
+            .children
+            .iter()
+            .flatten()
+            .map(|child| {
+                let dist = Dist::node_min_distance(
+                    point,
+                    child.data.centre,
+                    child.data.half_lengths,
+                );
+                (dist, child.as_ref())
+            })
+            .collect();
+        children.sort_by(|(a, _), (b, _)| {
+            a.partial_cmp(b).unwrap_or(Ordering::Equal)
+        });
+        children
+    }
+
+    /// Gets the items stored in every node whose box is intersected by the ray
+    /// starting at `origin` and travelling along `dir`, ordered front-to-back
+    /// by how far along the ray each item's centre lies.
+    ///
+    /// These are candidates rather than exact hits, as the items' geometry
+    /// isn't known. Children the ray misses are skipped.
+    #[must_use]
+    pub fn raycast(
+        &self,
+        origin: (S, S, S),
+        dir: (S, S, S),
+    ) -> Vec<D::ItemRef<'_>> {
+        let mut candidates = Vec::new();
+        self.raycast_into(origin, dir, &mut candidates);
+        candidates.sort_by(|(a, _), (b, _)| {
+            a.partial_cmp(b).unwrap_or(Ordering::Equal)
+        });
+        candidates.into_iter().map(|(_, item)| item).collect()
+    }
+
+    fn raycast_into<'a>(
+        &'a self,
+        origin: (S, S, S),
+        dir: (S, S, S),
+        result: &mut Vec<(S, D::ItemRef<'a>)>,
+    ) {
+        if !line_hits_box(
+            origin,
+            dir,
+            None,
+            self.data.centre,
+            self.data.half_lengths,
+        ) {
+            return;
+        }
+        result.extend(self.data.data.iter_items().map(|item| {
+            let (px, py, pz) = item.centre();
+            let t = (px - origin.0) * dir.0
+                + (py - origin.1) * dir.1
+                + (pz - origin.2) * dir.2;
+            (t, item)
+        }));
+        for child in self.children.iter().flatten() {
+            child.raycast_into(origin, dir, result);
+        }
+    }
+
+    /// Gets the items stored in every node whose box is intersected by the
+    /// segment from `a` to `b`. Unlike `raycast`, nodes beyond either end of
+    /// the segment are skipped, and if `a` equals `b` only the nodes whose box
+    /// contains that point (inclusive of its faces) are visited.
+    ///
+    /// As with `raycast`, these are candidates rather than exact hits.
+    #[must_use]
+    pub fn query_segment(
+        &self,
+        a: (S, S, S),
+        b: (S, S, S),
+    ) -> Vec<D::ItemRef<'_>> {
+        let dir = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+        self.query_by(|centre, half_lengths| {
+            line_hits_box(a, dir, Some(S::one()), centre, half_lengths)
+        })
+    }
+
+    /// Gets every item stored in the nodes visited by a custom traversal, for
+    /// queries against shapes not covered by the other methods.
+    ///
+    /// `descend` is given each node's centre and half lengths, starting with
+    /// this node, and returns whether that node should be visited. Children of
+    /// a node that isn't visited are skipped.
+    #[must_use]
+    pub fn query_by<F>(&self, descend: F) -> Vec<D::ItemRef<'_>>
+    where
+        F: Fn((S, S, S), (S, S, S)) -> bool,
+    {
+        let mut result = Vec::new();
+        self.query_by_into(&descend, &mut result);
+        result
+    }
+
+    fn query_by_into<'a, F>(
+        &'a self,
+        descend: &F,
+        result: &mut Vec<D::ItemRef<'a>>,
+    ) where
+        F: Fn((S, S, S), (S, S, S)) -> bool,
+    {
+        if !descend(self.data.centre, self.data.half_lengths) {
+            return;
+        }
+        result.extend(self.data.data.iter_items());
+        for child in self.children.iter().flatten() {
+            child.query_by_into(descend, result);
+        }
+    }
+
+    /// Calls `f` with each item whose centre lies within `radius` of `centre`,
+    /// pruning children in the same way as `query_radius` but without
+    /// collecting the items.
+    pub fn for_each_in_radius<F>(&self, centre: (S, S, S), radius: S, mut f: F)
+    where
+        F: FnMut(D::ItemRef<'_>),
+    {
+        self.visit_radius::<Euclidean, _>(centre, radius * radius, &mut f);
+    }
+
+    /// Calls `f` with each item whose centre lies within `max_dist` of `centre`
+    /// as measured by `Dist`, skipping children whose box lies further away.
+    fn visit_radius<'a, Dist, F>(
+        &'a self,
+        centre: (S, S, S),
+        max_dist: S,
+        f: &mut F,
+    ) where
+        Dist: Metric<S>,
+        F: FnMut(D::ItemRef<'a>),
+    {
+        self.data
+            .data
+            .iter_items()
+            .filter(|item| Dist::distance(item.centre(), centre) <= max_dist)
+            .for_each(&mut *f);
+        for child in self.children.iter().flatten() {
+            if Dist::node_min_distance(
+                centre,
+                child.data.centre,
+                child.data.half_lengths,
+            ) <= max_dist
+            {
+                child.visit_radius::<Dist, F>(centre, max_dist, f);
+            }
+        }
+    }
+}
+
+impl<T, S, M> ManagedVecOctree<T, S, M>
+where
+    T: CentredItem<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Calls `f` with a mutable reference to each item whose centre lies within
+    /// `radius` of `centre`, pruning children in the same way as
+    /// `query_radius`.
+    ///
+    /// Items stay in the node they were found in, so changing an item's centre
+    /// may leave it outside of that node's box.
+    pub fn for_each_in_radius_mut<F>(
+        &mut self,
+        centre: (S, S, S),
+        radius: S,
+        mut f: F,
+    ) where
+        F: FnMut(&mut T),
+    {
+        self.visit_radius_mut(centre, radius * radius, &mut f);
+    }
+
+    /// Removes and returns every item whose centre lies within `radius` of
+    /// `centre`, pruning children in the same way as `query_radius`.
+    pub fn drain_radius(&mut self, centre: (S, S, S), radius: S) -> Vec<T> {
+        let mut drained = Vec::new();
+        self.drain_radius_into(centre, radius * radius, &mut drained);
+        drained
+    }
+
+    fn drain_radius_into(
+        &mut self,
+        centre: (S, S, S),
+        radius_sq: S,
+        drained: &mut Vec<T>,
+    ) {
+        let before = drained.len();
+        let (inside, outside): (Vec<T>, Vec<T>) =
+            mem::take(&mut self.data.data)
+                .into_iter()
+                .partition(|item| {
+                    distance_sq(item.centre(), centre) <= radius_sq
+                });
+        self.data.data = outside;
+        drained.extend(inside);
+        for child in self.children.iter_mut().flatten() {
+            if box_distance_sq(
+                centre,
+                child.data.centre,
+                child.data.half_lengths,
+            ) <= radius_sq
+            {
+                child.drain_radius_into(centre, radius_sq, drained);
+            }
+        }
+        shrink_len(&mut self.data.len, drained.len() - before);
+    }
+
+    /// Gets every unordered pair of items whose centres are closer than
+    /// `radius`, each pair appearing once. This is useful as a collision
+    /// broad-phase.
+    ///
+    /// Each node's items are tested against each other and against the items
+    /// below it, and sibling subtrees are tested against each other, so pairs
+    /// split across nodes are found too. Subtrees whose boxes lie at least
+    /// `radius` apart are skipped.
+    #[must_use]
+    pub fn pairs_within(&self, radius: S) -> Vec<(&T, &T)> {
+        let mut pairs = Vec::new();
+        self.pairs_within_into(radius * radius, &mut pairs);
+        pairs
+    }
+
+    fn pairs_within_into<'a>(
+        &'a self,
+        radius_sq: S,
+        pairs: &mut Vec<(&'a T, &'a T)>,
+    ) {
+        let items = &self.data.data;
+        for (i, a) in items.iter().enumerate() {
+            for b in &items[i + 1..] {
+                if distance_sq(a.centre(), b.centre()) < radius_sq {
+                    pairs.push((a, b));
+                }
+            }
+            for child in self.children.iter().flatten() {
+                child.pair_with_subtree(a, radius_sq, pairs);
+            }
+        }
+        let children: Vec<&Self> =
+            self.children.iter().flatten().map(AsRef::as_ref).collect();
+        for (i, a) in children.iter().enumerate() {
+            a.pairs_within_into(radius_sq, pairs);
+            for b in &children[i + 1..] {
+                a.pair_subtrees(b, radius_sq, pairs);
+            }
+        }
+    }
+
+    /// Pairs `item` with each item in this subtree closer than the radius.
+    fn pair_with_subtree<'a>(
+        &'a self,
+        item: &'a T,
+        radius_sq: S,
+        pairs: &mut Vec<(&'a T, &'a T)>,
+    ) {
+        let centre = item.centre();
+        if box_distance_sq(centre, self.data.centre, self.data.half_lengths)
+            >= radius_sq
+        {
+            return;
+        }
+        for other in &self.data.data {
+            if distance_sq(centre, other.centre()) < radius_sq {
+                pairs.push((item, other));
+            }
+        }
+        for child in self.children.iter().flatten() {
+            child.pair_with_subtree(item, radius_sq, pairs);
+        }
+    }
+
+    /// Pairs the items in this subtree with those in the disjoint subtree
+    /// `other`.
+    fn pair_subtrees<'a>(
+        &'a self,
+        other: &'a Self,
+        radius_sq: S,
+        pairs: &mut Vec<(&'a T, &'a T)>,
+    ) {
+        if boxes_distance_sq(
+            (self.data.centre, self.data.half_lengths),
+            (other.data.centre, other.data.half_lengths),
+        ) >= radius_sq
+        {
+            return;
+        }
+        for item in &self.data.data {
+            other.pair_with_subtree(item, radius_sq, pairs);
+        }
+        for child in self.children.iter().flatten() {
+            child.pair_subtrees(other, radius_sq, pairs);
+        }
+    }
+
+    fn visit_radius_mut<F>(
+        &mut self,
+        centre: (S, S, S),
+        radius_sq: S,
+        f: &mut F,
+    ) where
+        F: FnMut(&mut T),
+    {
+        self.data
+            .data
+            .iter_mut()
+            .filter(|item| distance_sq(item.centre(), centre) <= radius_sq)
+            .for_each(&mut *f);
+        for child in self.children.iter_mut().flatten() {
+            if box_distance_sq(
+                centre,
+                child.data.centre,
+                child.data.half_lengths,
+            ) <= radius_sq
+            {
+                child.visit_radius_mut(centre, radius_sq, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use super::super::ManagedHashMapOctree;
+    use super::{
+        super::{CentredItem, ManagedVecOctree},
+        Axis,
+    };
+    use len_trait::Len;
+    use std::cell::Cell;
+
+    fn rebalanced_tree() -> ManagedVecOctree<(f32, f32, f32), f32> {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(2);
+        o.add((1.0, 1.0, 1.0));
+        o.add((2.0, 2.0, 1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        o
+    }
+
+    #[test]
+    fn test_query_radius_multiple_depths() {
+        let o = rebalanced_tree();
+        assert!(o.get_child_at_pos(true, true, true).is_some());
+        let mut result = o.query_radius((0.0, 0.0, 0.0), 2.0);
+        result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(result, vec![&(-1.0, -1.0, -1.0), &(1.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_count_in_radius_matches_query_radius() {
+        let o = grid_tree();
+        for &(centre, radius) in &[
+            ((0.0, 0.0, 0.0), 0.0),
+            ((0.0, 0.0, 0.0), 10.0),
+            ((5.0, 5.0, 5.0), 9.0),
+            ((-10.0, 10.0, 0.0), 15.0),
+            ((0.0, 0.0, 0.0), 100.0),
+        ] {
+            assert_eq!(
+                o.count_in_radius(centre, radius),
+                o.query_radius(centre, radius).len()
+            );
+        }
+        assert_eq!(o.count_in_radius((0.0, 0.0, 0.0), 100.0), 27);
+    }
+
+    #[test]
+    fn test_content_bounds() {
+        let o = rebalanced_tree();
+        assert_eq!(
+            o.content_bounds(),
+            Some(((-1.0, -1.0, -1.0), (600.0, 600.0, 600.0)))
+        );
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            100.0,
+        )
+        .with_max_size(1);
+        assert_eq!(o.content_bounds(), None);
+        o.extend(vec![
+            (3.0, -7.0, 2.0),
+            (-4.0, 5.0, 1.0),
+            (8.0, 0.0, -9.0),
+            (1.0, 1.0, 6.0),
+        ]);
+        o.rebalance();
+        assert_eq!(
+            o.content_bounds(),
+            Some(((-4.0, -7.0, -9.0), (8.0, 5.0, 6.0)))
+        );
+    }
+
+    #[test]
+    fn test_query_frustum_matches_query_aabb() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            100.0,
+        )
+        .with_max_size(2);
+        for &x in &[-50.0, -10.0, 0.0, 10.0, 50.0] {
+            for &y in &[-50.0, -10.0, 0.0, 10.0, 50.0] {
+                for &z in &[-50.0, -10.0, 0.0, 10.0, 50.0] {
+                    o.add((x, y, z));
+                }
+            }
+        }
+        o.rebalance();
+        let (min, max) = ((-10.0, -20.0, 0.0), (50.0, 10.0, 30.0));
+        let planes = [
+            (1.0, 0.0, 0.0, -min.0),
+            (-1.0, 0.0, 0.0, max.0),
+            (0.0, 1.0, 0.0, -min.1),
+            (0.0, -1.0, 0.0, max.1),
+            (0.0, 0.0, 1.0, -min.2),
+            (0.0, 0.0, -1.0, max.2),
+        ];
+        let mut frustum = o.query_frustum(planes);
+        let mut aabb = o.query_aabb(min, max);
+        frustum.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        aabb.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(frustum.len(), 4 * 3 * 2);
+        assert_eq!(frustum, aabb);
+    }
+
+    #[test]
+    fn test_drain_radius() {
+        let mut o = grid_tree();
+        let mut drained = o.drain_radius((10.0, 10.0, 10.0), 10.0);
+        drained.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            drained,
+            vec![
+                (0.0, 10.0, 10.0),
+                (10.0, 0.0, 10.0),
+                (10.0, 10.0, 0.0),
+                (10.0, 10.0, 10.0),
+            ]
+        );
+        assert_eq!(o.len(), 27 - 4);
+        assert_eq!(o.iter().count(), 27 - 4);
+        for (node, _) in o.iter_nodes() {
+            assert_eq!(node.len(), node.iter().count());
+        }
+        assert!(o.query_radius((10.0, 10.0, 10.0), 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_for_each_in_radius() {
+        let o = grid_tree();
+        let mut found = Vec::new();
+        o.for_each_in_radius((10.0, 10.0, 10.0), 10.0, |item| {
+            found.push(*item);
+        });
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            found,
+            vec![
+                (0.0, 10.0, 10.0),
+                (10.0, 0.0, 10.0),
+                (10.0, 10.0, 0.0),
+                (10.0, 10.0, 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_for_each_in_radius_mut() {
+        struct Particle {
+            pos: (f32, f32, f32),
+            hits: u32,
+        }
+
+        impl CentredItem<f32> for Particle {
+            fn centre(&self) -> (f32, f32, f32) { self.pos }
+        }
+
+        let mut o = ManagedVecOctree::<Particle, f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            100.0,
+        )
+        .with_max_size(4);
+        for pos in grid_tree() {
+            o.add(Particle { pos, hits: 0 });
+        }
+        o.rebalance();
+        let mut visited = 0;
+        o.for_each_in_radius_mut((10.0, 10.0, 10.0), 10.0, |particle| {
+            particle.hits += 1;
+            visited += 1;
+        });
+        assert_eq!(visited, 4);
+        assert_eq!(o.iter().filter(|particle| particle.hits == 1).count(), 4);
+        assert!(o.iter().all(|particle| particle.hits <= 1));
+    }
+
+    #[test]
+    fn test_integer_points_near_split_planes() {
+        let mut o =
+            ManagedVecOctree::<(i32, i32, i32), i32>::new_managed((0, 0, 0), 5)
+                .with_max_size(1);
+        let points = [
+            (5, 5, 5),
+            (1, 1, 1),
+            (0, 0, 0),
+            (-5, -5, -5),
+            (3, -2, 5),
+            (-1, 4, 2),
+            (2, 3, -3),
+        ];
+        o.extend(points.iter().copied());
+        o.rebalance();
+        assert!(o.depth() > 1);
+        for &p in &points {
+            assert_eq!(o.query_radius(p, 0), vec![&p]);
+            assert_eq!(o.query_aabb(p, p), vec![&p]);
+            assert_eq!(o.nearest(p), Some(&p));
+        }
+    }
+
+    #[test]
+    fn test_query_radius_zero_exact_match() {
+        let o = rebalanced_tree();
+        assert_eq!(
+            o.query_radius((2.0, 2.0, 1.0), 0.0),
+            vec![&(2.0, 2.0, 1.0)]
+        );
+        assert!(o.query_radius((2.0, 2.0, 2.0), 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_query_aabb_partial_pos_off_centre() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (100.0, 200.0, 300.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((600.0, -300.0, 800.0));
+        o.add((700.0, -200.0, 900.0));
+        o.add((-500.0, 500.0, 0.0));
+        o.rebalance();
+        assert!(o.get_child_at_pos(true, false, true).is_some());
+        let mut result =
+            o.query_aabb((550.0, -350.0, 750.0), (700.0, -200.0, 900.0));
+        result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            result,
+            vec![&(600.0, -300.0, 800.0), &(700.0, -200.0, 900.0)]
+        );
+        assert_eq!(
+            o.query_aabb((-600.0, 400.0, -100.0), (-400.0, 600.0, 100.0)),
+            vec![&(-500.0, 500.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_query_aabb_min_greater_than_max() {
+        let o = rebalanced_tree();
+        assert!(
+            o.query_aabb((10.0, -10.0, -10.0), (-10.0, 10.0, 10.0))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_min_pairwise_distance_sq_in_leaf() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1)
+        .with_max_depth(4);
+        for _ in 0..3 {
+            o.add((5.0, 5.0, 5.0));
+        }
+        o.add((-1.0, -1.0, -1.0));
+        o.rebalance();
+        assert_relative_eq!(
+            o.min_pairwise_distance_sq_in_leaf((5.0, 5.0, 5.0)).unwrap(),
+            0.0
+        );
+        assert_eq!(
+            o.min_pairwise_distance_sq_in_leaf((-1.0, -1.0, -1.0)),
+            None
+        );
+        assert_eq!(
+            o.min_pairwise_distance_sq_in_leaf((0.0, 0.0, 2000.0)),
+            None
+        );
+
+        let mut flat = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        flat.add((-1.0, -1.0, -1.0));
+        flat.add((-4.0, -1.0, -1.0));
+        flat.add((10.0, 10.0, 10.0));
+        assert_relative_eq!(
+            flat.min_pairwise_distance_sq_in_leaf((0.0, 0.0, 0.0))
+                .unwrap(),
+            9.0
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_min_pairwise_distance_in_leaf() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-4.0, -1.0, -1.0));
+        o.add((10.0, 10.0, 10.0));
+        assert_relative_eq!(
+            o.min_pairwise_distance_in_leaf((0.0, 0.0, 0.0)).unwrap(),
+            3.0
+        );
+        assert_eq!(o.min_pairwise_distance_in_leaf((0.0, 0.0, 2000.0)), None);
+    }
+
+    #[test]
+    fn test_query_radius_iter_matches_query_radius() {
+        let o = grid_tree();
+        for &(centre, radius) in &[
+            ((0.0, 0.0, 0.0), 150.0),
+            ((250.0, -250.0, 100.0), 300.0),
+            ((900.0, 900.0, 900.0), 10.0),
+        ] {
+            let lazy: Vec<_> = o.query_radius_iter(centre, radius).collect();
+            assert_eq!(lazy, o.query_radius(centre, radius));
+        }
+    }
+
+    #[test]
+    fn test_query_radius_iter_is_lazy() {
+        struct Counted<'a> {
+            pos: (f32, f32, f32),
+            visits: &'a Cell<usize>,
+        }
+
+        impl CentredItem<f32> for Counted<'_> {
+            fn centre(&self) -> (f32, f32, f32) {
+                self.visits.set(self.visits.get() + 1);
+                self.pos
+            }
+        }
+
+        let visits = Cell::new(0);
+        let mut o = ManagedVecOctree::<Counted<'_>, f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(4);
+        for i in 0..100_u8 {
+            let f = f32::from(i);
+            o.add(Counted {
+                pos: (f, f, f),
+                visits: &visits,
+            });
+        }
+        o.rebalance();
+        visits.set(0);
+
+        let mut iter = o.query_radius_iter((0.0, 0.0, 0.0), 1000.0);
+        assert!(iter.next().is_some());
+        assert!(visits.get() < 5);
+        assert_eq!(iter.count(), 99);
+    }
+
+    #[test]
+    fn test_nearest_empty() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        );
+        assert!(o.nearest((0.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_nearest_multiple_depths() {
+        let o = rebalanced_tree();
+        assert_eq!(o.nearest((1.5, 1.6, 1.0)), Some(&(2.0, 2.0, 1.0)));
+        assert_eq!(
+            o.nearest((500.0, 500.0, 500.0)),
+            Some(&(600.0, 600.0, 600.0))
+        );
+        // The closest item is buffered at the root rather than a child.
+        assert_eq!(o.nearest((-0.1, -0.1, -0.1)), Some(&(-1.0, -1.0, -1.0)));
+    }
+
+    #[test]
+    fn test_query_by_positive_x_half_space() {
+        let o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            10.0,
+        )
+        .with_max_size(1)
+        .with_items(vec![
+            (1.0, 1.0, 1.0),
+            (-1.0, -1.0, -1.0),
+            (5.0, -5.0, 5.0),
+            (-5.0, 5.0, -5.0),
+        ]);
+        let mut result = o.query_by(|(cx, ..), (hx, ..)| cx + hx > 0.0);
+        result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(result, vec![&(1.0, 1.0, 1.0), &(5.0, -5.0, 5.0)]);
+        assert!(o.query_by(|_, _| false).is_empty());
+        assert_eq!(o.query_by(|_, _| true).len(), 4);
+    }
+
+    fn grid_tree() -> ManagedVecOctree<(f32, f32, f32), f32> {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            100.0,
+        )
+        .with_max_size(4);
+        for &x in &[-10.0, 0.0, 10.0] {
+            for &y in &[-10.0, 0.0, 10.0] {
+                for &z in &[-10.0, 0.0, 10.0] {
+                    o.add((x, y, z));
+                }
+            }
+        }
+        o.rebalance();
+        o
+    }
+
+    #[test]
+    fn test_k_nearest_grid_order() {
+        let o = grid_tree();
+        assert_eq!(
+            o.k_nearest((2.0, 3.0, 4.0), 4),
+            vec![
+                &(0.0, 0.0, 0.0),
+                &(0.0, 0.0, 10.0),
+                &(0.0, 10.0, 0.0),
+                &(10.0, 0.0, 0.0),
+            ]
+        );
+        assert_eq!(
+            o.k_nearest((-9.0, -8.0, 7.0), 3),
+            vec![
+                &(-10.0, -10.0, 10.0),
+                &(-10.0, -10.0, 0.0),
+                &(-10.0, 0.0, 10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_k_nearest_fewer_than_k() {
+        let o = rebalanced_tree();
+        assert_eq!(o.k_nearest((0.0, 0.0, 0.0), 10).len(), 4);
+        assert!(o.k_nearest((0.0, 0.0, 0.0), 0).is_empty());
+    }
+
+    #[test]
+    fn test_raycast_along_x_axis() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((600.0, 1.0, 1.0));
+        o.add((500.0, -500.0, 500.0));
+        o.add((-300.0, 1.0, 1.0));
+        o.add((600.0, -600.0, 600.0));
+        o.add((100.0, 1.0, 1.0));
+        o.rebalance();
+        assert!(o.get_child(5).is_some());
+        assert_eq!(
+            o.raycast((-1000.0, 1.0, 1.0), (1.0, 0.0, 0.0)),
+            vec![&(-300.0, 1.0, 1.0), &(100.0, 1.0, 1.0), &(600.0, 1.0, 1.0),]
+        );
+        assert_eq!(
+            o.raycast((1000.0, 1.0, 1.0), (-1.0, 0.0, 0.0)),
+            vec![&(600.0, 1.0, 1.0), &(100.0, 1.0, 1.0), &(-300.0, 1.0, 1.0),]
+        );
+    }
+
+    #[test]
+    fn test_raycast_misses_root() {
+        let o = rebalanced_tree();
+        assert!(o.raycast((2000.0, 2000.0, 0.0), (1.0, 0.0, 0.0)).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_query_radius_hash() {
+        let mut o =
+            ManagedHashMapOctree::<u32, (f32, f32, f32), f32>::new_managed(
+                (0.0, 0.0, 0.0),
+                1000.0,
+            );
+        o.add((1, (1.0, 1.0, 1.0)));
+        o.add((2, (500.0, 500.0, 500.0)));
+        let result = o.query_radius((0.0, 0.0, 0.0), 10.0);
+        assert_eq!(result, vec![(&1, &(1.0, 1.0, 1.0))]);
+    }
+
+    #[test]
+    fn test_pairs_within_known_pairs() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((-1.0, 0.5, 0.5));
+        o.add((1.0, 0.5, 0.5));
+        o.add((2.5, 0.5, 0.5));
+        o.add((100.0, 100.0, 100.0));
+        o.add((100.0, 100.0, 101.5));
+        o.add((-500.0, -500.0, -500.0));
+        o.rebalance();
+        assert!(o.depth() > 2);
+
+        let mut pairs: Vec<_> = o
+            .pairs_within(2.01)
+            .into_iter()
+            .map(|(a, b)| if a < b { (*a, *b) } else { (*b, *a) })
+            .collect();
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            pairs,
+            vec![
+                ((-1.0, 0.5, 0.5), (1.0, 0.5, 0.5)),
+                ((1.0, 0.5, 0.5), (2.5, 0.5, 0.5)),
+                ((100.0, 100.0, 100.0), (100.0, 100.0, 101.5)),
+            ]
+        );
+        assert_eq!(o.pairs_within(2.0).len(), 2);
+    }
+
+    #[test]
+    fn test_pairs_within_matches_brute_force() {
+        let o = grid_tree();
+        let items: Vec<_> = o.iter().collect();
+        for &radius in &[0.0, 5.0, 10.5, 15.0, 100.0] {
+            let mut expected = 0;
+            for (i, a) in items.iter().enumerate() {
+                for b in &items[i + 1..] {
+                    let (dx, dy, dz) = (a.0 - b.0, a.1 - b.1, a.2 - b.2);
+                    if dx.hypot(dy).hypot(dz) < radius {
+                        expected += 1;
+                    }
+                }
+            }
+            assert_eq!(o.pairs_within(radius).len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_representative() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (10.0, 10.0, 10.0),
+            100.0,
+        );
+        assert_eq!(o.representative(), None);
+        o.add((0.0, 0.0, 0.0));
+        o.add((12.0, 8.0, 11.0));
+        o.add((10.0, 16.0, 10.0));
+        assert_eq!(o.representative(), Some(&(12.0, 8.0, 11.0)));
+
+        let mut o = o.with_max_size(1);
+        o.add((60.0, 60.0, 60.0));
+        o.rebalance();
+        let node = o.locate((0.0, 0.0, 0.0)).unwrap();
+        assert!(node.get_child(0).is_none());
+        assert_eq!(node.representative(), Some(&(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_query_segment_clamped() {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((-500.0, -500.0, -500.0));
+        o.add((-200.0, -200.0, -200.0));
+        o.add((-800.0, 800.0, -800.0));
+        o.add((-600.0, 600.0, -600.0));
+        o.add((500.0, 500.0, 500.0));
+        o.add((600.0, 600.0, 600.0));
+        o.rebalance();
+        assert!(o.data.data.is_empty());
+
+        let a = (-900.0, -900.0, -900.0);
+        let mut items = o.query_segment(a, (-100.0, -100.0, -100.0));
+        items.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            items,
+            vec![&(-500.0, -500.0, -500.0), &(-200.0, -200.0, -200.0)]
+        );
+        let mut items =
+            o.query_segment((900.0, 900.0, 900.0), (100.0, 100.0, 100.0));
+        items.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(items, vec![&(500.0, 500.0, 500.0), &(600.0, 600.0, 600.0)]);
+        assert_eq!(o.raycast(a, (1.0, 1.0, 1.0)).len(), 4);
+
+        let p = (-200.0, -200.0, -200.0);
+        assert_eq!(o.query_segment(p, p), vec![&p]);
+        assert!(
+            o.query_segment((2000.0, 0.0, 0.0), (1500.0, 0.0, 0.0))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_query_slab_matches_filter() {
+        let mut o = ManagedVecOctree::<(i32, i32, i32), i32>::new_managed(
+            (0, 0, 0),
+            64,
+        )
+        .with_max_size(2);
+        for x in (-60..60).step_by(13) {
+            for y in (-60..60).step_by(17) {
+                for z in (-60..60).step_by(7) {
+                    o.add((x, y, z));
+                }
+            }
+        }
+        o.rebalance();
+
+        let mut slab = o.query_slab(Axis::Z, 3, 10);
+        slab.sort_unstable();
+        let mut expected: Vec<_> =
+            o.iter().filter(|p| (3..=10).contains(&p.2)).collect();
+        expected.sort_unstable();
+        assert!(!expected.is_empty());
+        assert_eq!(slab, expected);
+
+        assert_eq!(o.query_slab(Axis::X, -100, 100).len(), o.len());
+        assert!(o.query_slab(Axis::Y, 10, -10).is_empty());
+    }
+}
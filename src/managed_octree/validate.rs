@@ -0,0 +1,191 @@
+use super::{CentredItem, ManagedOctree, OctreeCollectionRef};
+use alloc::vec::Vec;
+use core::ops::{Add, Div, Mul, Sub};
+use len_trait::{Empty, Len};
+use num::One;
+
+/// Returned by `validate` when a managed octree breaks one of its invariants.
+///
+/// Each variant holds the path of child indices from the validated node to
+/// the offending node, which is empty for the validated node itself.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvariantError<S> {
+    /// An item's centre lies outside of the bounds of the node holding it.
+    ItemOutOfBounds { path: Vec<usize>, point: (S, S, S) },
+    /// A node's stored `len` doesn't match the number of items in its
+    /// subtree.
+    LenMismatch {
+        path: Vec<usize>,
+        stored: usize,
+        actual: usize,
+    },
+    /// A node that could be split holds more items than its split threshold,
+    /// which is `max_size` unless raised with `with_split_threshold`. Items
+    /// too large for any of its children, or rejected by the child they
+    /// belong to, don't count towards the threshold.
+    OverCapacity {
+        path: Vec<usize>,
+        len: usize,
+        max_size: usize,
+    },
+}
+
+impl<D, S, M> ManagedOctree<D, S, M>
+where
+    D: Default + Empty + Len + OctreeCollectionRef<S>,
+    S: Default
+        + Copy
+        + One
+        + PartialOrd
+        + Add<S, Output = S>
+        + Sub<S, Output = S>
+        + Mul<S, Output = S>
+        + Div<S, Output = S>,
+    M: Default,
+{
+    /// Checks the invariants of this node and all of its descendants: every
+    /// item lies within the bounds of the node holding it (including all of
+    /// its faces), every node's `len` counts the items in its subtree, and no
+    /// node holds more than its split threshold unless it can't be split.
+    /// Items that can't be moved into a child, such as those too large for any
+    /// of them or whose key the child already holds, are left out of that
+    /// count.
+    ///
+    /// The last of these only holds once the tree has been rebalanced, as
+    /// `add` leaves items in this node.
+    ///
+    /// # Errors
+    /// Returns the first broken invariant found, visiting nodes depth-first.
+    pub fn validate(&self) -> Result<(), InvariantError<S>> {
+        self.validate_at(&mut Vec::new()).map(|_| ())
+    }
+
+    /// Validates the subtree at `path`, returning the number of items in it.
+    fn validate_at(
+        &self,
+        path: &mut Vec<usize>,
+    ) -> Result<usize, InvariantError<S>> {
+        let (min, max) = self.bounds();
+        for item in self.data.data.iter_items() {
+            let point = item.centre();
+            if point.0 < min.0
+                || point.0 > max.0
+                || point.1 < min.1
+                || point.1 > max.1
+                || point.2 < min.2
+                || point.2 > max.2
+            {
+                return Err(InvariantError::ItemOutOfBounds {
+                    path: path.clone(),
+                    point,
+                });
+            }
+        }
+        let own_len = self.data.data.len();
+        if self.movable_len() > self.split_threshold() && self.can_subdivide() {
+            return Err(InvariantError::OverCapacity {
+                path: path.clone(),
+                len: own_len,
+                max_size: self.split_threshold(),
+            });
+        }
+        let mut actual = own_len;
+        for (idx, child) in self.children() {
+            path.push(idx);
+            actual += child.validate_at(path)?;
+            path.pop();
+        }
+        if actual != self.data.len {
+            return Err(InvariantError::LenMismatch {
+                path: path.clone(),
+                stored: self.data.len,
+                actual,
+            });
+        }
+        Ok(actual)
+    }
+
+    /// Counts the items in this node's own buffer that `rebalance` could move
+    /// into a child.
+    fn movable_len(&self) -> usize {
+        self.data
+            .data
+            .iter_items()
+            .filter(|item| {
+                let centre = item.centre();
+                let idx = self.get_child_idx_for_point(centre);
+                self.fits_child(idx, centre, item.half_extents())
+                    && self.children[idx]
+                        .as_ref()
+                        .is_none_or(|child| child.data.data.accepts_ref(item))
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::ManagedVecOctree, InvariantError};
+
+    fn rebalanced_tree() -> ManagedVecOctree<(f32, f32, f32), f32> {
+        let mut o = ManagedVecOctree::<(f32, f32, f32), f32>::new_managed(
+            (0.0, 0.0, 0.0),
+            1000.0,
+        )
+        .with_max_size(1);
+        o.add((1.0, 1.0, 1.0));
+        o.add((600.0, 600.0, 600.0));
+        o.add((-1.0, -1.0, -1.0));
+        o.add((-2.0, -2.0, -2.0));
+        o.rebalance();
+        o
+    }
+
+    #[test]
+    fn test_validate_rebalanced() {
+        assert_eq!(rebalanced_tree().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_len_mismatch() {
+        let mut o = rebalanced_tree();
+        o.get_child_mut_at_pos(true, true, true).unwrap().data.len += 1;
+        assert_eq!(
+            o.validate(),
+            Err(InvariantError::LenMismatch {
+                path: vec![7],
+                stored: 3,
+                actual: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_over_capacity() {
+        let mut o = rebalanced_tree();
+        o.add((5.0, 5.0, 5.0));
+        o.add((6.0, 6.0, 6.0));
+        assert_eq!(
+            o.validate(),
+            Err(InvariantError::OverCapacity {
+                path: vec![],
+                len: 2,
+                max_size: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_item_out_of_bounds() {
+        let mut o = rebalanced_tree();
+        o.get_child_mut_at_pos(true, true, true)
+            .unwrap()
+            .get_data_mut()
+            .data
+            .push((-5.0, 5.0, 5.0));
+        assert!(matches!(
+            o.validate(),
+            Err(InvariantError::ItemOutOfBounds { path, .. }) if path == vec![7]
+        ));
+    }
+}